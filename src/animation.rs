@@ -0,0 +1,128 @@
+//! Sprite animations made of single images, loaded from toml files inside `assets/animations`
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{asset_loaders::AssetLoadError, util::deserialize_millis};
+
+/// The toml file of an animation
+#[derive(Deserialize, Debug)]
+pub struct AnimationConfig {
+    /// Whether the animation starts over after the last frame, otherwise the last frame is kept
+    #[serde(default)]
+    pub looping: bool,
+    pub frames: Vec<FrameConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FrameConfig {
+    /// The image of the frame, relative to the assets folder
+    pub sprite: PathBuf,
+    /// How long the frame is shown, a frame without duration is shown until the animation changes
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub texture: Handle<Image>,
+    pub duration: Duration,
+}
+
+/// A loaded animation, get it from the [`AssetCache`](crate::asset_loaders::cache::AssetCache)
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// The file the animation was loaded from, telling animations apart
+    path: PathBuf,
+    frames: Vec<AnimationFrame>,
+    looping: bool,
+}
+
+impl Animation {
+    /// Create a new animation, there has to be at least one frame
+    pub fn new(
+        path: &Path,
+        frames: Vec<AnimationFrame>,
+        looping: bool,
+    ) -> Result<Self, AssetLoadError> {
+        if frames.is_empty() {
+            return Err(AssetLoadError::EmptyAnimation(path.display().to_string()));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            frames,
+            looping,
+        })
+    }
+}
+
+/// Plays an animation on the sprite of the entity
+#[derive(Component, Debug)]
+pub struct Animator {
+    animation: Animation,
+    frame: usize,
+    /// How long the current frame is shown already
+    elapsed: Duration,
+}
+
+impl Animator {
+    pub fn new(animation: Animation) -> Self {
+        Self {
+            animation,
+            frame: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Switch to another animation, it is not restarted if it is playing already
+    pub fn play(&mut self, animation: &Animation) {
+        if self.animation.path != animation.path {
+            *self = Self::new(animation.clone());
+        }
+    }
+
+    /// The image of the current frame
+    pub fn texture(&self) -> Handle<Image> {
+        self.animation.frames[self.frame].texture.clone()
+    }
+
+    /// Move through the frames by the given time
+    fn advance(&mut self, delta: Duration) {
+        self.elapsed += delta;
+
+        loop {
+            let duration = self.animation.frames[self.frame].duration;
+            if duration.is_zero() || self.elapsed < duration {
+                return;
+            }
+
+            if self.frame + 1 < self.animation.frames.len() {
+                self.frame += 1;
+            } else if self.animation.looping {
+                self.frame = 0;
+            } else {
+                self.elapsed = duration;
+                return;
+            }
+            self.elapsed -= duration;
+        }
+    }
+}
+
+/// Plays the animations, switching the textures of the sprites
+pub fn animation_system(mut query: Query<(&mut Animator, &mut Handle<Image>)>, time: Res<Time>) {
+    for (mut animator, mut texture) in query.iter_mut() {
+        animator.advance(time.delta());
+
+        let current = &animator.animation.frames[animator.frame].texture;
+        if *texture != *current {
+            *texture = current.clone();
+        }
+    }
+}
@@ -39,6 +39,10 @@ pub enum AssetLoadError {
     InvalidPath,
     #[error("The given image asset could not be decoded")]
     DecodeImageError,
+    #[error("The given asset could not be parsed: {0}")]
+    ParseError(#[from] toml::de::Error),
+    #[error("The animation has no frames: {0}")]
+    EmptyAnimation(String),
 }
 
 impl<T: RustEmbed> EmbeddedAssetLoader for T {
@@ -4,11 +4,15 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::asset_loaders::{AssetLoadError, EmbeddedAssetLoader};
+use crate::{
+    animation::{Animation, AnimationConfig, AnimationFrame},
+    asset_loaders::{AssetLoadError, EmbeddedAssetLoader},
+};
 
 pub struct AssetCache<T: EmbeddedAssetLoader> {
     _phantom: PhantomData<T>,
     cache: HashMap<PathBuf, Handle<Image>>,
+    animations: HashMap<PathBuf, Animation>,
 }
 
 impl<T: EmbeddedAssetLoader> AssetCache<T> {
@@ -16,6 +20,7 @@ impl<T: EmbeddedAssetLoader> AssetCache<T> {
         Self {
             _phantom: PhantomData,
             cache: HashMap::new(),
+            animations: HashMap::new(),
         }
     }
 
@@ -34,4 +39,33 @@ impl<T: EmbeddedAssetLoader> AssetCache<T> {
             }
         }
     }
+
+    /// Load an animation toml and all of its frames
+    pub fn load_animation<P: AsRef<Path>>(
+        &mut self,
+        assets: &mut Assets<Image>,
+        path: P,
+    ) -> Result<Animation, AssetLoadError> {
+        let path = path.as_ref();
+        if let Some(animation) = self.animations.get(path) {
+            return Ok(animation.clone());
+        }
+
+        let config: AnimationConfig = toml::from_slice(&T::load(path)?)?;
+        let frames = config
+            .frames
+            .iter()
+            .map(|frame| {
+                Ok(AnimationFrame {
+                    texture: self.load_image(assets, &frame.sprite)?,
+                    duration: frame.duration,
+                })
+            })
+            .collect::<Result<Vec<_>, AssetLoadError>>()?;
+
+        let animation = Animation::new(path, frames, config.looping)?;
+        self.animations
+            .insert(path.to_path_buf(), animation.clone());
+        Ok(animation)
+    }
 }
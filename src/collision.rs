@@ -1,5 +1,9 @@
 //! Simple implementation of collisions
 
+pub mod bodies;
+pub mod raycast;
+pub mod triggers;
+
 use std::{
     cmp::Ordering,
     ops::{BitAnd, BitOr, Not},
@@ -9,6 +13,7 @@ use bevy::{prelude::*, sprite::collide_aabb::Collision};
 use serde::{Deserialize, Serialize};
 
 use crate::physics::VelocityMap;
+use bodies::Body;
 
 #[derive(Debug)]
 pub struct CollisionEvent {
@@ -47,6 +52,12 @@ pub struct Collider {
     /// The actual size of the collider
     pub size: Vec2,
     pub filter: CollisionFilter,
+    /// The layers this collider is part of
+    pub layers: CollisionLayers,
+    /// The layers this collider collides with
+    pub mask: CollisionLayers,
+    /// The surface of the collider, affecting everything standing on it
+    pub material: SurfaceMaterial,
     /// The offset used for collision "delay",
     ///
     /// If for instance collision from top is measured, the finite line used for intersection
@@ -59,11 +70,48 @@ impl Default for Collider {
         Self {
             size: Vec2::ZERO,
             filter: CollisionFilter::ALL,
+            layers: CollisionLayers::WORLD,
+            mask: CollisionLayers::ALL,
+            material: SurfaceMaterial::default(),
             collision_offset: Vec2::ZERO,
         }
     }
 }
 
+impl Collider {
+    /// Whether this collider and the other collider collide with each other
+    pub fn interacts_with(&self, other: &Collider) -> bool {
+        self.interacts_with_layers(other.layers, other.mask)
+    }
+
+    /// Whether this collider collides with something inside `layers` which collides with `mask`
+    pub fn interacts_with_layers(&self, layers: CollisionLayers, mask: CollisionLayers) -> bool {
+        self.mask.intersects(layers) && mask.intersects(self.layers)
+    }
+}
+
+/// The physical properties of the surface of a collider
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SurfaceMaterial {
+    /// How fast the horizontal velocity of things standing on it changes, 1 changes instantly
+    pub friction: f32,
+    /// How much of the falling speed is kept when bouncing off the surface
+    pub restitution: f32,
+    /// The horizontal speed everything standing on it is moved with
+    pub conveyor_speed: f32,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        Self {
+            friction: 1.0,
+            restitution: 0.0,
+            conveyor_speed: 0.0,
+        }
+    }
+}
+
 /// A movable collider which should not pass through
 #[derive(Component, Debug)]
 pub struct MoveableCollider;
@@ -77,8 +125,7 @@ impl CollisionFilter {
     pub const RIGHT: Self = Self(0b0100);
     pub const BOTTOM: Self = Self(0b0010);
     pub const LEFT: Self = Self(0b0001);
-    pub const BULLETS: Self = Self(0b10000);
-    pub const ALL: Self = Self(0b11111);
+    pub const ALL: Self = Self(0b1111);
 
     // pub fn with(self, other: Self) -> Self {
     //     self | other
@@ -104,10 +151,7 @@ impl CollisionFilter {
         self.collides_with(Self::LEFT)
     }
 
-    pub fn collides_with_bullets(self) -> bool {
-        self.collides_with(Self::BULLETS)
-    }
-
+    #[allow(dead_code)] // NOTE will be used later
     pub fn is_collision(self, col: &Collision) -> bool {
         match col {
             Collision::Left => self.collides_left(),
@@ -144,15 +188,98 @@ impl Not for CollisionFilter {
     }
 }
 
+/// A named collision layer, used for configuring the layers of a collider
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionLayer {
+    World,
+    Player,
+    Enemy,
+    PlayerProjectile,
+    EnemyProjectile,
+}
+
+/// A set of collision layers, deserialized from a list of [`CollisionLayer`] names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "Vec<CollisionLayer>")]
+pub struct CollisionLayers(u8);
+
+impl CollisionLayers {
+    pub const WORLD: Self = Self(0b00001);
+    pub const PLAYER: Self = Self(0b00010);
+    pub const ENEMY: Self = Self(0b00100);
+    pub const PLAYER_PROJECTILE: Self = Self(0b01000);
+    pub const ENEMY_PROJECTILE: Self = Self(0b10000);
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(0b11111);
+
+    /// Whether at least one layer is part of both sets
+    pub fn intersects(self, other: CollisionLayers) -> bool {
+        (self & other) != Self::NONE
+    }
+}
+
+impl From<CollisionLayer> for CollisionLayers {
+    fn from(layer: CollisionLayer) -> Self {
+        match layer {
+            CollisionLayer::World => Self::WORLD,
+            CollisionLayer::Player => Self::PLAYER,
+            CollisionLayer::Enemy => Self::ENEMY,
+            CollisionLayer::PlayerProjectile => Self::PLAYER_PROJECTILE,
+            CollisionLayer::EnemyProjectile => Self::ENEMY_PROJECTILE,
+        }
+    }
+}
+
+impl From<Vec<CollisionLayer>> for CollisionLayers {
+    fn from(layers: Vec<CollisionLayer>) -> Self {
+        layers
+            .into_iter()
+            .fold(Self::NONE, |acc, layer| acc | Self::from(layer))
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for CollisionLayers {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Not for CollisionLayers {
+    type Output = Self;
+    fn not(self) -> Self::Output {
+        Self(!self.0 & Self::ALL.0)
+    }
+}
+
 #[allow(clippy::too_many_lines)] // NOTE may be changed later
 pub fn collision_system(
     // mut meshes: ResMut<Assets<Mesh>>,
-    moving_query: Query<(&Transform, &Collider, &VelocityMap, Entity), With<MoveableCollider>>,
-    collider_query: Query<(&Transform, &Collider, Entity)>,
+    moving_query: Query<
+        (&Transform, &Collider, &VelocityMap, Option<&Body>, Entity),
+        With<MoveableCollider>,
+    >,
+    collider_query: Query<(
+        &Transform,
+        &Collider,
+        Option<&VelocityMap>,
+        Option<&Body>,
+        Entity,
+    )>,
     mut wcollision_events: EventWriter<CollisionEvent>,
 ) {
     // Check line intersection
-    for (moving_trans, moving_collider, velocity_map, moving_entity) in moving_query.iter() {
+    for (moving_trans, moving_collider, velocity_map, moving_body, moving_entity) in
+        moving_query.iter()
+    {
         // player_move points
         let start_pos = moving_trans.translation.truncate() - velocity_map.last_velocity();
         let end_pos = moving_trans.translation.truncate();
@@ -162,11 +289,22 @@ pub fn collision_system(
         let mut vertical_collisions: Vec<CollisionWith> = vec![];
         let mut horizontal_collisions: Vec<CollisionWith> = vec![];
 
-        for (collider_trans, collider, static_entity) in collider_query.iter() {
-            if moving_entity == static_entity {
+        for (collider_trans, collider, collider_velocity_map, static_body, static_entity) in
+            collider_query.iter()
+        {
+            // Bodies collide with each other in `bodies::body_collision_system`
+            if moving_entity == static_entity
+                || !moving_collider.interacts_with(collider)
+                || (moving_body.is_some() && static_body.is_some())
+            {
                 continue;
             }
 
+            // The collider may have moved itself (e.g. moving platforms), so the line is measured
+            // relative to the colliders position in the last frame
+            let start_pos = collider_velocity_map
+                .map_or(start_pos, |vel_map| start_pos + vel_map.last_velocity());
+
             // rect points
             // rect center
             let coll_center = collider_trans.translation.truncate();
@@ -360,6 +498,10 @@ pub fn collision_system(
     }
 }
 
+/// Label of the system moving the moveable colliders out of the colliders they collided with
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CollisionMoveLabel;
+
 #[derive(Debug)]
 struct CollisionWith {
     static_entity: Entity,
@@ -0,0 +1,190 @@
+//! Collisions between moveable colliders, which push each other out instead of passing through
+
+use bevy::{prelude::*, sprite::collide_aabb::Collision};
+use serde::Deserialize;
+
+use super::{Collider, CollisionEvent, MoveOn, MoveableCollider};
+use crate::physics::{VelocityId, VelocityMap};
+
+/// The configuration of a body tile inside the `main.toml`
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BodyConfig {
+    #[serde(default = "default_body_mass")]
+    mass: f32,
+    #[serde(default)]
+    pushable: bool,
+}
+
+fn default_body_mass() -> f32 {
+    1.0
+}
+
+/// Label of the system resolving the collisions between bodies, the [`CollisionEvent`]s of bodies
+/// standing on each other are only sent after it
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BodyCollisionLabel;
+
+/// A moveable collider colliding with other bodies, other moveable colliders only collide with
+/// bodies like with static colliders
+#[derive(Component, Debug)]
+pub struct Body {
+    /// The velocity of other bodies pushing this one
+    vel_id: VelocityId,
+    /// How hard it is to push the body, relative to the body pushing it
+    mass: f32,
+    /// Whether other bodies can push the body, otherwise it blocks them
+    pushable: bool,
+}
+
+impl Body {
+    /// Create a new body inside the given [`VelocityMap`]
+    pub fn new_in(vel_map: &mut VelocityMap, mass: f32, pushable: bool) -> Self {
+        Self {
+            vel_id: vel_map.register().0,
+            mass,
+            pushable,
+        }
+    }
+
+    pub fn from_config_in(vel_map: &mut VelocityMap, config: &BodyConfig) -> Self {
+        Self::new_in(vel_map, config.mass, config.pushable)
+    }
+}
+
+/// Moves overlapping bodies out of each other and lets bodies push the pushable ones.
+///
+/// Each body is moved back by the part of the overlap it caused by moving into the other body,
+/// so moving out of each other never moves a body somewhere it hasn't been before.
+/// Pushed bodies are moved with their push velocity in the next frame, which still collides
+/// with the static colliders.
+pub fn body_collision_system(
+    mut query: Query<
+        (&mut Transform, &Collider, &mut VelocityMap, &Body, Entity),
+        With<MoveableCollider>,
+    >,
+    mut collision_writer: EventWriter<CollisionEvent>,
+) {
+    for (_, _, mut vel_map, body, _) in query.iter_mut() {
+        if let Err(e) = vel_map.set(body.vel_id, Vec2::ZERO) {
+            panic!(
+                "{} -> Body velocity not inside map, you forgot to register it",
+                e
+            );
+        }
+    }
+
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([a, b]) = combinations.fetch_next() {
+        let (mut trans_a, collider_a, mut vel_map_a, body_a, entity_a) = a;
+        let (mut trans_b, collider_b, mut vel_map_b, body_b, entity_b) = b;
+
+        if !collider_a.interacts_with(collider_b) {
+            continue;
+        }
+
+        let delta = trans_b.translation.truncate() - trans_a.translation.truncate();
+        let overlap = (collider_a.size + collider_b.size) / 2.0 - delta.abs();
+        if overlap.x <= 0.0 || overlap.y <= 0.0 {
+            continue;
+        }
+
+        let velocity_a = vel_map_a.last_velocity();
+        let velocity_b = vel_map_b.last_velocity();
+
+        if overlap.x < overlap.y {
+            // 1 if b is right of a
+            let dir = if delta.x < 0.0 { -1.0 } else { 1.0 };
+            let approach_a = (velocity_a.x * dir).max(0.0);
+            let approach_b = (-velocity_b.x * dir).max(0.0);
+            let (share_a, share_b) = overlap_shares(approach_a, approach_b).unwrap_or((0.5, 0.5));
+
+            trans_a.translation.x -= dir * overlap.x * share_a;
+            trans_b.translation.x += dir * overlap.x * share_b;
+
+            if body_b.pushable && approach_a > 0.0 {
+                push(
+                    &mut vel_map_b,
+                    body_b,
+                    velocity_a.x * mass_share(body_a, body_b),
+                );
+            }
+            if body_a.pushable && approach_b > 0.0 {
+                push(
+                    &mut vel_map_a,
+                    body_a,
+                    velocity_b.x * mass_share(body_b, body_a),
+                );
+            }
+
+            let (side_a, side_b) = if dir > 0.0 {
+                (Collision::Left, Collision::Right)
+            } else {
+                (Collision::Right, Collision::Left)
+            };
+            send_collisions(
+                &mut collision_writer,
+                (entity_a, side_a),
+                (entity_b, side_b),
+            );
+        } else {
+            // 1 if b is above a
+            let dir = if delta.y < 0.0 { -1.0 } else { 1.0 };
+            let approach_a = (velocity_a.y * dir).max(0.0);
+            let approach_b = (-velocity_b.y * dir).max(0.0);
+            // Without moving into each other, the upper body is put onto the lower one
+            let (share_a, share_b) = overlap_shares(approach_a, approach_b)
+                .unwrap_or(if dir > 0.0 { (0.0, 1.0) } else { (1.0, 0.0) });
+
+            trans_a.translation.y -= dir * overlap.y * share_a;
+            trans_b.translation.y += dir * overlap.y * share_b;
+
+            let (side_a, side_b) = if dir > 0.0 {
+                (Collision::Bottom, Collision::Top)
+            } else {
+                (Collision::Top, Collision::Bottom)
+            };
+            send_collisions(
+                &mut collision_writer,
+                (entity_a, side_a),
+                (entity_b, side_b),
+            );
+        }
+    }
+}
+
+/// The parts of the overlap caused by each body, `None` if neither moved into the other
+fn overlap_shares(approach_a: f32, approach_b: f32) -> Option<(f32, f32)> {
+    let approach = approach_a + approach_b;
+    (approach > 0.0).then(|| (approach_a / approach, approach_b / approach))
+}
+
+/// The part of the velocity of `pusher` which is passed on to `pushed`
+fn mass_share(pusher: &Body, pushed: &Body) -> f32 {
+    pusher.mass / (pusher.mass + pushed.mass)
+}
+
+fn push(vel_map: &mut VelocityMap, body: &Body, velocity_x: f32) {
+    if let Some(vel) = vel_map.get_mut(body.vel_id) {
+        vel.x += velocity_x;
+    }
+}
+
+/// Sends the collisions of both bodies, each colliding with `side` of the other one
+fn send_collisions(
+    collision_writer: &mut EventWriter<CollisionEvent>,
+    (entity_a, side_a): (Entity, Collision),
+    (entity_b, side_b): (Entity, Collision),
+) {
+    collision_writer.send(CollisionEvent {
+        collision: side_a,
+        moving_entity: entity_a,
+        static_entity: entity_b,
+        move_on: MoveOn::None,
+    });
+    collision_writer.send(CollisionEvent {
+        collision: side_b,
+        moving_entity: entity_b,
+        static_entity: entity_a,
+        move_on: MoveOn::None,
+    });
+}
@@ -0,0 +1,196 @@
+//! Casting rays and boxes against all colliders
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{line_intersection, Collider, CollisionLayers};
+
+/// Tolerance for intersections lying exactly on the corners of a collider
+const CAST_EPSILON: f32 = 0.001;
+
+/// The first collider hit by a cast
+#[allow(dead_code)] // NOTE will be used later
+#[derive(Debug, Clone, Copy)]
+pub struct CastHit {
+    pub entity: Entity,
+    /// The point of the cast origin when hitting the collider
+    pub point: Vec2,
+    /// The normal of the side of the collider which was hit
+    pub normal: Vec2,
+    /// The distance from the origin to the `point`
+    pub distance: f32,
+}
+
+/// Decides which colliders can be hit by a cast
+#[derive(Debug, Clone, Copy)]
+pub struct CastFilter {
+    /// The layers of the cast, colliders not colliding with them are ignored
+    pub layers: CollisionLayers,
+    /// The layers the cast collides with
+    pub mask: CollisionLayers,
+    /// An entity which is never hit, normally the casting entity itself
+    pub ignore: Option<Entity>,
+}
+
+impl CastFilter {
+    pub fn new(layers: CollisionLayers, mask: CollisionLayers) -> Self {
+        Self {
+            layers,
+            mask,
+            ignore: None,
+        }
+    }
+
+    /// Never hit the given entity
+    pub fn ignoring(mut self, entity: Entity) -> Self {
+        self.ignore = Some(entity);
+        self
+    }
+}
+
+impl Default for CastFilter {
+    fn default() -> Self {
+        Self::new(CollisionLayers::ALL, CollisionLayers::ALL)
+    }
+}
+
+/// Query to cast rays and boxes against all colliders.
+///
+/// Only the sides of colliders facing the cast are hit and only if they collide on that side
+/// according to their [`CollisionFilter`](super::CollisionFilter), so a cast starting inside of
+/// a collider does not hit it.
+#[derive(SystemParam)]
+pub struct ColliderCast<'w, 's> {
+    collider_query: Query<'w, 's, (&'static Transform, &'static Collider, Entity)>,
+}
+
+impl<'w, 's> ColliderCast<'w, 's> {
+    /// Cast a ray from `origin` along `direction` up to `max_distance`
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        filter: &CastFilter,
+    ) -> Option<CastHit> {
+        self.boxcast(origin, Vec2::ZERO, direction, max_distance, filter)
+    }
+
+    /// Cast a box of `size` centered at `origin` along `direction` up to `max_distance`
+    pub fn boxcast(
+        &self,
+        origin: Vec2,
+        size: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        filter: &CastFilter,
+    ) -> Option<CastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        let end = origin + direction * max_distance;
+        let mut closest_hit: Option<CastHit> = None;
+
+        for (collider_trans, collider, entity) in self.collider_query.iter() {
+            if filter.ignore == Some(entity)
+                || !collider.interacts_with_layers(filter.layers, filter.mask)
+            {
+                continue;
+            }
+
+            // Casting a box against a collider is the same as casting a ray against the collider
+            // grown by the size of the box
+            let center = collider_trans.translation.truncate();
+            let half_size = (collider.size + size) / 2.0;
+            let bottom_left = center - half_size;
+            let top_right = center + half_size;
+            let top_left = Vec2::new(bottom_left.x, top_right.y);
+            let bottom_right = Vec2::new(top_right.x, bottom_left.y);
+
+            let sides = [
+                (collider.filter.collides_top(), top_left, top_right, Vec2::Y),
+                (
+                    collider.filter.collides_right(),
+                    bottom_right,
+                    top_right,
+                    Vec2::X,
+                ),
+                (
+                    collider.filter.collides_bottom(),
+                    bottom_left,
+                    bottom_right,
+                    Vec2::NEG_Y,
+                ),
+                (
+                    collider.filter.collides_left(),
+                    bottom_left,
+                    top_left,
+                    Vec2::NEG_X,
+                ),
+            ];
+
+            for (collides, side_start, side_end, normal) in sides {
+                if !collides || direction.dot(normal) >= 0.0 {
+                    continue;
+                }
+
+                if let Some(point) = segment_intersection(origin, end, side_start, side_end) {
+                    let distance = origin.distance(point);
+                    if closest_hit.map_or(true, |hit| distance < hit.distance) {
+                        closest_hit = Some(CastHit {
+                            entity,
+                            point,
+                            normal,
+                            distance,
+                        });
+                    }
+                }
+            }
+        }
+
+        closest_hit
+    }
+
+    /// The first collider overlapping a box of `size` centered at `center`, touching is no overlap.
+    ///
+    /// Colliders without a bottom side, like one-way platforms, are ignored, since the box can
+    /// grow into them from below.
+    pub fn overlap(&self, center: Vec2, size: Vec2, filter: &CastFilter) -> Option<Entity> {
+        self.collider_query
+            .iter()
+            .find(|(collider_trans, collider, entity)| {
+                if filter.ignore == Some(*entity)
+                    || !collider.filter.collides_bottom()
+                    || !collider.interacts_with_layers(filter.layers, filter.mask)
+                {
+                    return false;
+                }
+
+                let distance = (collider_trans.translation.truncate() - center).abs();
+                let overlap = (collider.size + size) / 2.0 - distance;
+                overlap.cmpgt(Vec2::splat(CAST_EPSILON)).all()
+            })
+            .map(|(_, _, entity)| entity)
+    }
+}
+
+/// Intersection of two finite lines
+fn segment_intersection(
+    start_pos: Vec2,
+    end_pos: Vec2,
+    coll_start: Vec2,
+    coll_end: Vec2,
+) -> Option<Vec2> {
+    line_intersection(start_pos, end_pos, coll_start, coll_end).filter(|intersection| {
+        is_on_segment(*intersection, start_pos, end_pos)
+            && is_on_segment(*intersection, coll_start, coll_end)
+    })
+}
+
+fn is_on_segment(point: Vec2, start: Vec2, end: Vec2) -> bool {
+    let min = start.min(end) - Vec2::splat(CAST_EPSILON);
+    let max = start.max(end) + Vec2::splat(CAST_EPSILON);
+
+    point.cmpge(min).all() && point.cmple(max).all()
+}
@@ -0,0 +1,114 @@
+//! Trigger areas notifying about moveable colliders entering, staying inside and leaving them
+
+use bevy::{prelude::*, sprite::collide_aabb, utils::HashSet};
+
+use super::{Collider, CollisionLayers, MoveableCollider};
+
+/// A non solid area sending [`TriggerEvent`]s for every moveable collider overlapping it
+#[derive(Component, Debug)]
+pub struct Trigger {
+    pub size: Vec2,
+    pub offset: Vec3,
+    /// The collision layers of the moveable colliders noticed by the trigger, all by default
+    pub mask: CollisionLayers,
+    /// All moveable colliders inside the area at the end of the last frame
+    inside: HashSet<Entity>,
+}
+
+impl Trigger {
+    pub fn new(size: Vec2, offset: Vec3) -> Self {
+        Self {
+            size,
+            offset,
+            mask: CollisionLayers::ALL,
+            inside: HashSet::default(),
+        }
+    }
+
+    /// Only notice the moveable colliders inside the layers of `mask`
+    pub fn with_mask(mut self, mask: CollisionLayers) -> Self {
+        self.mask = mask;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEventKind {
+    /// The entity started overlapping the trigger this frame
+    Enter,
+    /// The entity was overlapping the trigger before and still is
+    Stay,
+    /// The entity stopped overlapping the trigger this frame
+    Exit,
+}
+
+#[derive(Debug)]
+pub struct TriggerEvent {
+    pub kind: TriggerEventKind,
+    pub trigger: Entity,
+    /// The moveable collider entering, staying inside or leaving the trigger
+    pub entity: Entity,
+}
+
+impl TriggerEvent {
+    /// Whether the entity is inside the trigger, either by entering or staying
+    pub fn is_inside(&self) -> bool {
+        matches!(self.kind, TriggerEventKind::Enter | TriggerEventKind::Stay)
+    }
+}
+
+/// Sends the trigger events for all moveable colliders after the collisions were resolved
+pub fn trigger_system(
+    mut trigger_query: Query<(&Transform, &mut Trigger, Entity)>,
+    moving_query: Query<(&Transform, &Collider, Entity), With<MoveableCollider>>,
+    mut trigger_writer: EventWriter<TriggerEvent>,
+) {
+    for (trigger_trans, mut trigger, trigger_entity) in trigger_query.iter_mut() {
+        let mut inside = HashSet::with_capacity(trigger.inside.len());
+
+        for (moving_trans, moving_collider, moving_entity) in moving_query.iter() {
+            if !trigger.mask.intersects(moving_collider.layers) {
+                continue;
+            }
+
+            let collision = collide_aabb::collide(
+                moving_trans.translation,
+                moving_collider.size,
+                trigger_trans.translation + trigger.offset,
+                trigger.size,
+            );
+
+            if collision.is_none() {
+                continue;
+            }
+
+            let kind = if trigger.inside.contains(&moving_entity) {
+                TriggerEventKind::Stay
+            } else {
+                TriggerEventKind::Enter
+            };
+
+            trigger_writer.send(TriggerEvent {
+                kind,
+                trigger: trigger_entity,
+                entity: moving_entity,
+            });
+            inside.insert(moving_entity);
+        }
+
+        // Despawned entities don't leave the trigger, the readers would use them otherwise
+        for entity in trigger
+            .inside
+            .difference(&inside)
+            .filter(|entity| moving_query.get(**entity).is_ok())
+        {
+            trigger_writer.send(TriggerEvent {
+                kind: TriggerEventKind::Exit,
+                trigger: trigger_entity,
+                entity: *entity,
+            });
+        }
+
+        trigger.inside = inside;
+    }
+}
@@ -2,13 +2,23 @@
 
 use bevy::prelude::*;
 
-use crate::physics::{VelocityId, VelocityMap};
+use crate::{
+    collision::{triggers::TriggerEvent, Collider, CollisionEvent, CollisionLayers},
+    physics::{VelocityId, VelocityMap},
+};
+
+/// The knockback strength of contact damage, if not set otherwise
+const DEFAULT_KNOCKBACK: f32 = 5.0;
 
 /// A projectile moved along its direction
 #[derive(Debug, Component)]
 pub struct Projectile {
     pub direction: Vec2,
     pub speed: f32,
+    /// The collision layers of the projectile
+    pub layers: CollisionLayers,
+    /// The collision layers the projectile hits
+    pub mask: CollisionLayers,
     pub(crate) vel_id: Option<VelocityId>,
 }
 
@@ -28,6 +38,76 @@ pub fn move_projectile_system(mut projectiles: Query<(&mut VelocityMap, &mut Pro
     }
 }
 
+/// Damage dealt to an entity
+#[derive(Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// The entity dealing the damage
+    #[allow(dead_code)] // NOTE will be used later
+    pub source: Entity,
+    pub amount: u32,
+    /// The velocity the target is pushed away with
+    pub knockback: Vec2,
+}
+
+/// Damages everything touching the entity, either by colliding with it or inside its trigger
+#[derive(Component, Debug)]
+pub struct ContactDamage {
+    pub amount: u32,
+    pub knockback: f32,
+    /// The collision layers of the colliders which are damaged
+    pub targets: CollisionLayers,
+}
+
+impl ContactDamage {
+    /// Damage the player with the default knockback
+    pub fn new(amount: u32) -> Self {
+        Self {
+            amount,
+            knockback: DEFAULT_KNOCKBACK,
+            targets: CollisionLayers::PLAYER,
+        }
+    }
+}
+
+/// Sends the [`DamageEvent`]s for everything touching a [`ContactDamage`]
+pub fn contact_damage_system(
+    mut collision_reader: EventReader<CollisionEvent>,
+    mut trigger_reader: EventReader<TriggerEvent>,
+    damage_query: Query<(&ContactDamage, &Transform)>,
+    target_query: Query<(&Collider, &Transform)>,
+    mut damage_writer: EventWriter<DamageEvent>,
+) {
+    let collisions = collision_reader
+        .iter()
+        .map(|evt| (evt.static_entity, evt.moving_entity));
+    let triggers = trigger_reader
+        .iter()
+        .filter(|evt| evt.is_inside())
+        .map(|evt| (evt.trigger, evt.entity));
+
+    for (source, target) in collisions.chain(triggers) {
+        if let (Ok((damage, source_trans)), Ok((target_collider, target_trans))) =
+            (damage_query.get(source), target_query.get(target))
+        {
+            if !damage.targets.intersects(target_collider.layers) {
+                continue;
+            }
+
+            // Always pushed a bit upwards, so the target is not pushed into the ground
+            let away = (target_trans.translation - source_trans.translation)
+                .truncate()
+                .normalize_or_zero();
+            damage_writer.send(DamageEvent {
+                target,
+                source,
+                amount: damage.amount,
+                knockback: (away + Vec2::Y).normalize_or_zero() * damage.knockback,
+            });
+        }
+    }
+}
+
 // pub struct EnemyHurtBox {
 //     size: Vec2
 // }
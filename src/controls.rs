@@ -0,0 +1,206 @@
+//! Maps the keys, mouse buttons and gamepad inputs to the actions of the player, loaded from the
+//! `controls.toml`
+//!
+//! Systems read the actions from the [`Input<Action>`] resource instead of the raw input, so
+//! everything can be rebound.
+
+use bevy::{input::InputSystem, prelude::*, window::CursorMoved};
+use serde::Deserialize;
+
+use crate::{
+    asset_loaders::{EmbeddedAssetLoader, EmbeddedData},
+    player::abilities::EquipSlot,
+};
+
+#[derive(Debug)]
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Controls::load("controls.toml"))
+            .init_resource::<Input<Action>>()
+            .init_resource::<Aim>()
+            .add_system_to_stage(CoreStage::PreUpdate, aim_system.after(InputSystem))
+            .add_system_to_stage(CoreStage::PreUpdate, action_input_system.after(InputSystem));
+    }
+}
+
+/// Everything the player can do by pressing a key or a button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    /// Use the ability equipped at the slot
+    Use(EquipSlot),
+    /// Equip the ability the player stands at to the slot
+    Equip(EquipSlot),
+    /// Stop charging the abilities
+    Cancel,
+}
+
+impl Action {
+    const ALL: [Self; 8] = [
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::Jump,
+        Self::Use(EquipSlot::Left),
+        Self::Use(EquipSlot::Right),
+        Self::Equip(EquipSlot::Left),
+        Self::Equip(EquipSlot::Right),
+        Self::Cancel,
+    ];
+}
+
+/// How far a stick has to be pushed to press the actions bound to it
+const STICK_THRESHOLD: f32 = 0.5;
+/// How far the right stick has to be pushed to switch to aiming with it
+const STICK_AIM_DEADZONE: f32 = 0.3;
+
+/// A single key or button an action is bound to
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button of any connected gamepad
+    Button(GamepadButtonType),
+    /// A stick of any connected gamepad, pushed in the direction along the axis
+    Axis(GamepadAxisType, AxisDirection),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+impl AxisDirection {
+    fn is_pushed(self, value: f32) -> bool {
+        match self {
+            Self::Positive => value > STICK_THRESHOLD,
+            Self::Negative => value < -STICK_THRESHOLD,
+        }
+    }
+}
+
+/// The bindings of every action, each action can be bound to multiple keys and buttons
+#[derive(Deserialize, Debug)]
+pub struct Controls {
+    move_left: Vec<Binding>,
+    move_right: Vec<Binding>,
+    jump: Vec<Binding>,
+    use_left: Vec<Binding>,
+    use_right: Vec<Binding>,
+    equip_left: Vec<Binding>,
+    equip_right: Vec<Binding>,
+    cancel: Vec<Binding>,
+}
+
+impl Controls {
+    pub fn load(filename: &str) -> Self {
+        let data = EmbeddedData::load(filename).unwrap_or_else(|e| {
+            panic!(
+                "The controls config({}) could not be loaded: {}",
+                filename, e
+            )
+        });
+
+        toml::from_slice(&data).unwrap_or_else(|e| {
+            panic!(
+                "There was an error parsing the controls config({}): {}",
+                filename, e
+            )
+        })
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        match action {
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::Jump => &self.jump,
+            Action::Use(EquipSlot::Left) => &self.use_left,
+            Action::Use(EquipSlot::Right) => &self.use_right,
+            Action::Equip(EquipSlot::Left) => &self.equip_left,
+            Action::Equip(EquipSlot::Right) => &self.equip_right,
+            Action::Cancel => &self.cancel,
+        }
+    }
+}
+
+/// Presses and releases the actions by their bindings
+pub fn action_input_system(
+    mut actions: ResMut<Input<Action>>,
+    controls: Res<Controls>,
+    kb_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    button_input: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+) {
+    actions.clear();
+
+    for action in Action::ALL {
+        let pressed = controls
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => kb_input.pressed(key),
+                Binding::Mouse(button) => mouse_input.pressed(button),
+                Binding::Button(button) => gamepads
+                    .iter()
+                    .any(|gamepad| button_input.pressed(GamepadButton(*gamepad, button))),
+                Binding::Axis(axis, direction) => gamepads.iter().any(|gamepad| {
+                    axes.get(GamepadAxis(*gamepad, axis))
+                        .map_or(false, |value| direction.is_pushed(value))
+                }),
+            });
+
+        if pressed {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+/// What the [`MouseCursor`](crate::player::MouseCursor) follows, switched by the input used last
+#[derive(Debug, Clone, Copy)]
+pub enum Aim {
+    /// The cursor of the window
+    Mouse,
+    /// The direction of the right stick, around the player
+    Stick(Vec2),
+}
+
+impl Default for Aim {
+    fn default() -> Self {
+        Self::Mouse
+    }
+}
+
+/// Switches to aiming with the mouse when it moves and to the stick when it is pushed
+pub fn aim_system(
+    mut aim: ResMut<Aim>,
+    mut cursor_reader: EventReader<CursorMoved>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+) {
+    if cursor_reader.iter().last().is_some() {
+        *aim = Aim::Mouse;
+    }
+
+    for gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            axes.get(GamepadAxis(*gamepad, GamepadAxisType::RightStickX))
+                .unwrap_or(0.0),
+            axes.get(GamepadAxis(*gamepad, GamepadAxisType::RightStickY))
+                .unwrap_or(0.0),
+        );
+
+        if stick.length() > STICK_AIM_DEADZONE {
+            *aim = Aim::Stick(stick.normalize());
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Debug overlay drawing the outlines of colliders, triggers and collisions and logging the
+//! player state transitions
+
+use bevy::{prelude::*, sprite::collide_aabb::Collision};
+
+use crate::{
+    collision::{
+        bodies::BodyCollisionLabel, triggers::Trigger, Collider, CollisionEvent, MoveableCollider,
+    },
+    physics::VelocityMap,
+    player::state::PlayerStateEvent,
+    COLLISION_STAGE, POST_COLLISION_STAGE,
+};
+
+/// The key toggling the debug overlay
+const DEBUG_TOGGLE_KEY: KeyCode = KeyCode::F1;
+/// The z position of the overlay, in front of everything but the camera
+const DEBUG_Z: f32 = 9.0;
+const DEBUG_LINE_WIDTH: f32 = 0.5;
+
+const COLLIDING_SIDE_COLOR: Color = Color::GREEN;
+const PASSING_SIDE_COLOR: Color = Color::rgba(0.5, 0.5, 0.5, 0.4);
+const MOVEABLE_COLOR: Color = Color::CYAN;
+const TRIGGER_COLOR: Color = Color::YELLOW;
+const SWEEP_COLOR: Color = Color::ORANGE;
+const COLLISION_COLOR: Color = Color::FUCHSIA;
+
+#[derive(Debug)]
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_system(debug_toggle_system)
+            .add_system(debug_player_state_system)
+            .add_system_to_stage(COLLISION_STAGE, debug_sweep_system)
+            .add_system_to_stage(
+                POST_COLLISION_STAGE,
+                debug_overlay_system.after(BodyCollisionLabel),
+            );
+    }
+}
+
+/// State of the debug overlay
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    enabled: bool,
+    /// The lines moving colliders were moved along this frame
+    sweeps: Vec<(Vec2, Vec2)>,
+}
+
+/// Marks the shapes drawn by the overlay, which are redrawn every frame
+#[derive(Component, Debug)]
+pub struct DebugShape;
+
+fn debug_toggle_system(mut overlay: ResMut<DebugOverlay>, kb_input: Res<Input<KeyCode>>) {
+    if kb_input.just_pressed(DEBUG_TOGGLE_KEY) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn debug_player_state_system(
+    overlay: Res<DebugOverlay>,
+    mut state_reader: EventReader<PlayerStateEvent>,
+) {
+    for evt in state_reader.iter() {
+        if overlay.enabled {
+            info!("Player {:?}: {:?} -> {:?}", evt.player, evt.from, evt.to);
+        }
+    }
+}
+
+/// Records the lines used by `collision_system`, before the colliders are moved out of each other
+fn debug_sweep_system(
+    mut overlay: ResMut<DebugOverlay>,
+    moving_query: Query<(&Transform, &VelocityMap), With<MoveableCollider>>,
+) {
+    overlay.sweeps.clear();
+    if !overlay.enabled {
+        return;
+    }
+
+    for (transform, vel_map) in moving_query.iter() {
+        let end_pos = transform.translation.truncate();
+        overlay
+            .sweeps
+            .push((end_pos - vel_map.last_velocity(), end_pos));
+    }
+}
+
+fn debug_overlay_system(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    shape_query: Query<Entity, With<DebugShape>>,
+    collider_query: Query<(&Transform, &Collider, Option<&MoveableCollider>)>,
+    trigger_query: Query<(&Transform, &Trigger)>,
+    mut collision_reader: EventReader<CollisionEvent>,
+) {
+    for shape in shape_query.iter() {
+        commands.entity(shape).despawn();
+    }
+
+    let collisions: Vec<&CollisionEvent> = collision_reader.iter().collect();
+    if !overlay.enabled {
+        return;
+    }
+
+    for (transform, collider, moveable) in collider_query.iter() {
+        let [top, right, bottom, left] =
+            rect_sides(transform.translation.truncate(), collider.size);
+        let side_color = |collides: bool| match (collides, moveable) {
+            (false, _) => PASSING_SIDE_COLOR,
+            (true, Some(_)) => MOVEABLE_COLOR,
+            (true, None) => COLLIDING_SIDE_COLOR,
+        };
+
+        spawn_line(
+            &mut commands,
+            top,
+            side_color(collider.filter.collides_top()),
+        );
+        spawn_line(
+            &mut commands,
+            right,
+            side_color(collider.filter.collides_right()),
+        );
+        spawn_line(
+            &mut commands,
+            bottom,
+            side_color(collider.filter.collides_bottom()),
+        );
+        spawn_line(
+            &mut commands,
+            left,
+            side_color(collider.filter.collides_left()),
+        );
+    }
+
+    for (transform, trigger) in trigger_query.iter() {
+        for side in rect_sides(
+            (transform.translation + trigger.offset).truncate(),
+            trigger.size,
+        ) {
+            spawn_line(&mut commands, side, TRIGGER_COLOR);
+        }
+    }
+
+    for sweep in overlay.sweeps.iter() {
+        spawn_line(&mut commands, *sweep, SWEEP_COLOR);
+    }
+
+    for evt in collisions {
+        if let Ok((transform, collider, _)) = collider_query.get(evt.static_entity) {
+            let [top, right, bottom, left] =
+                rect_sides(transform.translation.truncate(), collider.size);
+            let side = match evt.collision {
+                Collision::Top => top,
+                Collision::Right => right,
+                Collision::Bottom => bottom,
+                Collision::Left => left,
+                Collision::Inside => continue,
+            };
+            spawn_line(&mut commands, side, COLLISION_COLOR);
+        }
+    }
+}
+
+/// The top, right, bottom and left side of a rectangle
+fn rect_sides(center: Vec2, size: Vec2) -> [(Vec2, Vec2); 4] {
+    let bottom_left = center - size / 2.0;
+    let top_right = center + size / 2.0;
+    let top_left = Vec2::new(bottom_left.x, top_right.y);
+    let bottom_right = Vec2::new(top_right.x, bottom_left.y);
+
+    [
+        (top_left, top_right),
+        (bottom_right, top_right),
+        (bottom_left, bottom_right),
+        (bottom_left, top_left),
+    ]
+}
+
+fn spawn_line(commands: &mut Commands, (start, end): (Vec2, Vec2), color: Color) {
+    let line = end - start;
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(
+                    line.length() + DEBUG_LINE_WIDTH,
+                    DEBUG_LINE_WIDTH,
+                )),
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: ((start + end) / 2.0).extend(DEBUG_Z),
+                rotation: Quat::from_rotation_z(line.y.atan2(line.x)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(DebugShape);
+}
@@ -1,42 +1,72 @@
 mod slime;
 
 use std::{
-    fmt::Debug,
+    borrow::Cow,
+    fmt::{self, Debug, Display, Formatter},
     ops::{Sub, SubAssign},
+    time::Duration,
 };
 
 use bevy::{prelude::*, utils::HashMap};
 
-use once_cell::sync::Lazy;
 use serde::Deserialize;
 
-use self::slime::{slime_run_system, slime_turn_around_system, GreenSlime};
+use self::slime::SlimePlugin;
 use crate::{
     asset_loaders::{cache::AssetCache, EmbeddedAssets},
-    POST_COLLISION_STAGE,
+    util::TimeWindow,
 };
 
-pub static ENEMY_MAP: Lazy<HashMap<EnemyKind, EnemyDescriptor>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    map.insert(EnemyKind::GreenSlime, GreenSlime::enemy_descriptor());
-
-    map
-});
-
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(slime_run_system)
+        app.init_resource::<EnemyRegistry>()
             .add_system(enemy_die_system)
-            .add_system_to_stage(POST_COLLISION_STAGE, slime_turn_around_system);
+            .add_plugin(SlimePlugin);
+    }
+}
+
+/// The id of an enemy as it is used in the `map.toml`
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[serde(transparent)]
+pub struct EnemyKind(Cow<'static, str>);
+
+impl Display for EnemyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// The direction an enemy faces
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum EnemyKind {
-    GreenSlime,
+pub enum Facing {
+    Left,
+    Right,
+}
+
+/// The parameters of a single spawned enemy, set for the tile in the `map.toml` and per color in
+/// the `room.toml`. Every parameter is optional, the enemy decides what to do without it.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct EnemyParams {
+    /// The direction the enemy faces when spawned
+    pub facing: Option<Facing>,
+    /// How many tiles the enemy walks away from its spawn point to both sides
+    pub patrol_range: Option<f32>,
+    /// Replaces the base health of the enemy
+    pub health: Option<u32>,
+}
+
+impl EnemyParams {
+    /// Override the parameters with the ones set in `other`
+    pub fn overridden_by(self, other: &Self) -> Self {
+        Self {
+            facing: other.facing.or(self.facing),
+            patrol_range: other.patrol_range.or(self.patrol_range),
+            health: other.health.or(self.health),
+        }
+    }
 }
 
 pub trait Enemy: Component {
@@ -45,6 +75,7 @@ pub trait Enemy: Component {
         image_assets: &mut Assets<Image>,
         asset_cache: &mut AssetCache<EmbeddedAssets>,
         at: Vec3,
+        params: &EnemyParams,
     ) -> Entity;
 }
 
@@ -61,8 +92,13 @@ impl<T: Enemy> EnemyExt for T {}
 
 pub struct EnemyDescriptor {
     pub name: &'static str,
-    pub spawn_at:
-        fn(&mut Commands, &mut Assets<Image>, &mut AssetCache<EmbeddedAssets>, Vec3) -> Entity,
+    pub spawn_at: fn(
+        &mut Commands,
+        &mut Assets<Image>,
+        &mut AssetCache<EmbeddedAssets>,
+        Vec3,
+        &EnemyParams,
+    ) -> Entity,
 }
 
 impl EnemyDescriptor {
@@ -72,8 +108,9 @@ impl EnemyDescriptor {
         image_assets: &mut Assets<Image>,
         asset_cache: &mut AssetCache<EmbeddedAssets>,
         at: Vec3,
+        params: &EnemyParams,
     ) -> Entity {
-        (self.spawn_at)(commands, image_assets, asset_cache, at)
+        (self.spawn_at)(commands, image_assets, asset_cache, at, params)
     }
 }
 
@@ -83,6 +120,38 @@ impl Debug for EnemyDescriptor {
     }
 }
 
+/// The enemies by their kind, each enemy registers itself from its own plugin
+#[derive(Debug, Default)]
+pub struct EnemyRegistry(HashMap<EnemyKind, EnemyDescriptor>);
+
+impl EnemyRegistry {
+    pub fn get(&self, kind: &EnemyKind) -> Option<&EnemyDescriptor> {
+        self.0.get(kind)
+    }
+}
+
+pub trait RegisterEnemy {
+    /// Register the enemy `T` to be spawned for the tiles of the kind `kind_id`
+    fn register_enemy<T: Enemy>(&mut self, kind_id: &'static str) -> &mut Self;
+}
+
+impl RegisterEnemy for App {
+    fn register_enemy<T: Enemy>(&mut self, kind_id: &'static str) -> &mut Self {
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(EnemyRegistry::default);
+        if registry
+            .0
+            .insert(EnemyKind(Cow::Borrowed(kind_id)), T::enemy_descriptor())
+            .is_some()
+        {
+            panic!("The enemy {} is registered twice", kind_id);
+        }
+
+        self
+    }
+}
+
 #[derive(Component)]
 pub struct EnemyHealth(u32);
 
@@ -106,6 +175,24 @@ impl SubAssign<u32> for EnemyHealth {
     }
 }
 
+/// Keeps an enemy from moving on its own for a while
+#[derive(Component, Debug, Default)]
+pub struct EnemyStun {
+    stunned: TimeWindow,
+    duration: Duration,
+}
+
+impl EnemyStun {
+    pub fn stun(&mut self, duration: Duration) {
+        self.stunned.start();
+        self.duration = duration;
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.stunned.is_within(self.duration)
+    }
+}
+
 pub fn enemy_die_system(mut commands: Commands, query: Query<(&EnemyHealth, Entity)>) {
     for (enemy, entity) in query.iter() {
         if enemy.0 == 0 {
@@ -2,26 +2,56 @@ use bevy::{prelude::*, sprite::collide_aabb::Collision};
 
 use crate::{
     asset_loaders::{cache::AssetCache, EmbeddedAssets},
-    collision::{Collider, CollisionEvent, CollisionFilter, MoveableCollider},
-    map::TILE_SIZE,
-    physics::{Gravity, VelocityId, VelocityMap},
+    collision::{
+        bodies::{Body, BodyCollisionLabel},
+        raycast::{CastFilter, ColliderCast},
+        Collider, CollisionEvent, CollisionFilter, CollisionLayers, CollisionMoveLabel,
+        MoveableCollider, SurfaceMaterial,
+    },
+    combat::ContactDamage,
+    map::{platforms::PlatformRider, TILE_SIZE},
+    physics::{volumes::VolumeEffects, Gravity, GroundContact, VelocityId, VelocityMap},
+    POST_COLLISION_STAGE,
 };
 
-use super::{Enemy, EnemyHealth};
+use super::{Enemy, EnemyHealth, EnemyParams, EnemyStun, Facing, RegisterEnemy};
 
+/// The health of slimes whose tile or room sets none in their `enemy_params`
 const SLIME_BASE_HEALTH: u32 = 8;
 
+pub struct SlimePlugin;
+
+impl Plugin for SlimePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_enemy::<GreenSlime>("green_slime")
+            .add_system(slime_run_system)
+            .add_system_to_stage(
+                POST_COLLISION_STAGE,
+                slime_turn_around_system.after(BodyCollisionLabel),
+            )
+            .add_system_to_stage(
+                POST_COLLISION_STAGE,
+                slime_ledge_system.after(CollisionMoveLabel),
+            );
+    }
+}
+
 #[derive(Component)]
 pub struct GreenSlime {
     walking_left: bool,
     vel_id: VelocityId,
+    /// The lowest and highest x position the slime walks between
+    patrol: Option<(f32, f32)>,
 }
 
 impl GreenSlime {
-    fn new(vel_map: &mut VelocityMap) -> Self {
+    fn new(vel_map: &mut VelocityMap, at: Vec3, params: &EnemyParams) -> Self {
         Self {
-            walking_left: true,
+            walking_left: params.facing != Some(Facing::Right),
             vel_id: vel_map.register().0,
+            patrol: params
+                .patrol_range
+                .map(|range| (at.x - range * TILE_SIZE, at.x + range * TILE_SIZE)),
         }
     }
 }
@@ -32,6 +62,7 @@ impl Enemy for GreenSlime {
         image_assets: &mut Assets<Image>,
         asset_cache: &mut AssetCache<EmbeddedAssets>,
         at: Vec3,
+        params: &EnemyParams,
     ) -> Entity {
         let texture = asset_cache
             .load_image(image_assets, "sprites/enemies/slime.png")
@@ -44,29 +75,57 @@ impl Enemy for GreenSlime {
                 transform: Transform::from_translation(at + Vec3::new(0.0, TILE_SIZE / 2.0, 0.0)),
                 ..Default::default()
             })
-            .insert(GreenSlime::new(&mut vel_map))
+            .insert(GreenSlime::new(&mut vel_map, at, params))
             .insert(Gravity::new_in(&mut vel_map))
+            .insert(PlatformRider::new_in(&mut vel_map))
+            .insert(GroundContact::new_in(&mut vel_map))
+            .insert(VolumeEffects::new_in(&mut vel_map))
+            .insert(Body::new_in(&mut vel_map, 1.0, false))
             .insert(Collider {
                 filter: CollisionFilter::ALL,
+                layers: CollisionLayers::ENEMY,
+                mask: CollisionLayers::ALL,
+                material: SurfaceMaterial::default(),
                 size: Vec2::new(8.0, 16.0),
                 collision_offset: Vec2::ZERO,
             })
             .insert(MoveableCollider)
             .insert(vel_map)
-            .insert(EnemyHealth::new(SLIME_BASE_HEALTH))
+            .insert(EnemyHealth::new(params.health.unwrap_or(SLIME_BASE_HEALTH)))
+            .insert(ContactDamage::new(1))
+            .insert(EnemyStun::default())
             .id()
     }
 }
 
-pub fn slime_run_system(mut query: Query<(&GreenSlime, &mut Transform, &mut VelocityMap)>) {
+pub fn slime_run_system(
+    mut query: Query<(
+        &mut GreenSlime,
+        &GroundContact,
+        &EnemyStun,
+        &mut Transform,
+        &mut VelocityMap,
+    )>,
+) {
     const SLIME_SPEED: f32 = 1.0;
-    for (slime, mut trans, mut vel_map) in query.iter_mut() {
+    for (mut slime, ground, stun, mut trans, mut vel_map) in query.iter_mut() {
+        // Turn around at the ends of the patrol
+        if let Some((min_x, max_x)) = slime.patrol {
+            if trans.translation.x <= min_x {
+                slime.walking_left = false;
+            } else if trans.translation.x >= max_x {
+                slime.walking_left = true;
+            }
+        }
+
         if let Some(vel) = vel_map.get_mut(slime.vel_id) {
-            if slime.walking_left {
-                vel.x = -SLIME_SPEED;
+            if stun.is_stunned() {
+                vel.x = ground.approach(vel.x, 0.0);
+            } else if slime.walking_left {
+                vel.x = ground.approach(vel.x, -SLIME_SPEED);
                 trans.rotation = Quat::from_axis_angle(Vec3::Y, 0.0);
             } else {
-                vel.x = SLIME_SPEED;
+                vel.x = ground.approach(vel.x, SLIME_SPEED);
                 trans.rotation = Quat::from_axis_angle(Vec3::Y, 180.0f32.to_radians());
             }
         } else {
@@ -89,3 +148,39 @@ pub fn slime_turn_around_system(
         }
     }
 }
+
+/// Makes the slimes turn around before walking off a ledge
+pub fn slime_ledge_system(
+    mut query: Query<(&mut GreenSlime, &Transform, &Collider, Entity)>,
+    colliders: ColliderCast,
+) {
+    const SLIME_LEDGE_DEPTH: f32 = TILE_SIZE / 2.0;
+
+    for (mut slime, transform, collider, entity) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let half_size = collider.size / 2.0;
+        let filter = CastFilter::new(collider.layers, collider.mask).ignoring(entity);
+        let ground_distance = half_size.y + SLIME_LEDGE_DEPTH;
+
+        // Falling slimes can't walk off a ledge
+        if colliders
+            .raycast(position, Vec2::NEG_Y, ground_distance, &filter)
+            .is_none()
+        {
+            continue;
+        }
+
+        let front = if slime.walking_left {
+            position - Vec2::new(half_size.x, 0.0)
+        } else {
+            position + Vec2::new(half_size.x, 0.0)
+        };
+
+        if colliders
+            .raycast(front, Vec2::NEG_Y, ground_distance, &filter)
+            .is_none()
+        {
+            slime.walking_left = !slime.walking_left;
+        }
+    }
+}
@@ -5,10 +5,13 @@
 //! # Panics
 //! When the initial room could not be loaded
 
+mod animation;
 mod asset_loaders;
 mod camera;
 mod collision;
 mod combat;
+mod controls;
+mod debug;
 mod enemies;
 mod map;
 mod physics;
@@ -21,11 +24,21 @@ use bevy_framepace::{FramepacePlugin, FramepaceSettings, Limiter};
 
 use asset_loaders::{cache::AssetCache, EmbeddedAssets};
 use camera::FollowEntity;
-use collision::CollisionEvent;
-use enemies::EnemyPlugin;
-use map::{connections, LoadRoomConfig, MapManager};
-use physics::{PhysicsPlugin, VEL_MOVE_STAGE};
-use player::{MouseCursor, PlayerPlugin};
+use collision::{
+    bodies::{self, BodyCollisionLabel},
+    triggers::{self, TriggerEvent},
+    CollisionEvent, CollisionMoveLabel,
+};
+use combat::DamageEvent;
+use controls::ControlsPlugin;
+use debug::DebugPlugin;
+use enemies::{EnemyPlugin, EnemyRegistry};
+use map::{connections, platforms, LoadRoomConfig, MapManager};
+use physics::{PhysicsPlugin, VEL_MOVE_STAGE, VEL_SYSTEM_STAGE};
+use player::{
+    abilities::{registry::AbilityRegistry, AbilityPlugins},
+    MouseCursor, PlayerPlugin,
+};
 
 const PLAYER_SIZE: f32 = 16.0;
 
@@ -54,17 +67,43 @@ fn main() {
             POST_COLLISION_STAGE,
             SystemStage::parallel(),
         )
+        .add_plugin(ControlsPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugins(AbilityPlugins)
         .add_plugin(EnemyPlugin)
+        .add_plugin(DebugPlugin)
         .add_startup_system(setup_system)
         .add_startup_system(initial_room_setup)
         .add_startup_system(grab_mouse)
         .add_system(combat::move_projectile_system)
+        .add_system(combat::contact_damage_system)
+        .add_system_to_stage(CoreStage::PostUpdate, animation::animation_system)
         .add_system_to_stage(CAMERA_MOVE_STAGE, camera::camera_follow_system)
         .add_system_to_stage(COLLISION_STAGE, collision::collision_system)
-        .add_system_to_stage(POST_COLLISION_STAGE, collision::collision_move_system)
-        .add_system(connections::connection_collision_system)
+        .add_system_to_stage(
+            POST_COLLISION_STAGE,
+            collision::collision_move_system.label(CollisionMoveLabel),
+        )
+        .add_system_to_stage(
+            POST_COLLISION_STAGE,
+            bodies::body_collision_system
+                .label(BodyCollisionLabel)
+                .after(CollisionMoveLabel),
+        )
+        .add_system_to_stage(
+            POST_COLLISION_STAGE,
+            triggers::trigger_system.after(CollisionMoveLabel),
+        )
+        .add_system(connections::connection_trigger_system)
+        .add_system(platforms::moving_platform_system)
+        .add_system_to_stage(
+            POST_COLLISION_STAGE,
+            platforms::platform_rider_system.after(BodyCollisionLabel),
+        )
+        .add_system_to_stage(VEL_SYSTEM_STAGE, platforms::platform_carry_system)
         .add_event::<CollisionEvent>()
+        .add_event::<TriggerEvent>()
+        .add_event::<DamageEvent>()
         .insert_resource(AssetCache::<EmbeddedAssets>::new())
         .insert_resource(MapManager::load_map("maps/main.toml", "demo".into()))
         .run();
@@ -102,11 +141,15 @@ fn initial_room_setup(
     mut asset_cache: ResMut<AssetCache<EmbeddedAssets>>,
     mut assets: ResMut<Assets<Image>>,
     mut map_manager: ResMut<MapManager>,
+    enemies: Res<EnemyRegistry>,
+    abilities: Res<AbilityRegistry>,
 ) {
     if let Err(e) = map_manager.load_room(
         &mut asset_cache,
         &mut assets,
         &mut commands,
+        &enemies,
+        &abilities,
         LoadRoomConfig {
             section: None,
             // room: "tt_need_earth".into(),
@@ -1,6 +1,10 @@
 pub mod connections;
+pub mod platforms;
 
-use bevy::{prelude::*, sprite::collide_aabb::Collision, utils::HashMap};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 use std::{
     borrow::Cow,
     path::{Path, PathBuf},
@@ -12,12 +16,28 @@ use thiserror::Error;
 
 use crate::{
     asset_loaders::{AssetLoadError, EmbeddedAssetLoader, EmbeddedAssets, EmbeddedData},
-    collision::{BreakableCollider, Collider, CollisionFilter},
-    enemies::{EnemyKind, ENEMY_MAP},
-    player::abilities::{collectibles::CollectibleAbilityTrigger, AbilityItem, ABILITY_MAP},
+    collision::{
+        bodies::{Body, BodyConfig},
+        triggers::Trigger,
+        BreakableCollider, Collider, CollisionFilter, CollisionLayers, MoveableCollider,
+        SurfaceMaterial,
+    },
+    combat::ContactDamage,
+    enemies::{EnemyKind, EnemyParams, EnemyRegistry},
+    physics::{
+        volumes::{Volume, VolumeConfig, VolumeEffects},
+        Gravity, GroundContact, SectionGravity, VelocityMap,
+    },
+    player::abilities::{
+        collectibles::{CollectibleAbilityTrigger, CombineAltar},
+        registry::AbilityRegistry,
+        upgrades::CollectibleUpgradeTrigger,
+        AbilityItem,
+    },
     AssetCache,
 };
 use connections::Connection;
+use platforms::{MovingPlatform, PlatformConfig, PlatformRider};
 
 pub type Colors = HashMap<String, String>;
 
@@ -33,6 +53,8 @@ pub struct MapManager {
     room_stack: Vec<Room>,
     map: Map,
     current_section: Section,
+    /// The collected items, which are not spawned again when their room is loaded
+    collected: HashSet<TileId>,
 }
 
 impl MapManager {
@@ -53,6 +75,7 @@ impl MapManager {
             }) {
             Ok(section) => Section {
                 colors: section.colors,
+                gravity: section.gravity,
                 name: section_name,
             },
             Err(e) => panic!("Failure to load section {}", e),
@@ -62,14 +85,27 @@ impl MapManager {
             room_stack: Vec::with_capacity(2),
             map,
             current_section,
+            collected: HashSet::default(),
         }
     }
 
+    /// The entity of the current room, everything spawned as its child is removed with the room
+    pub fn current_room(&self) -> Option<Entity> {
+        self.room_stack.last().map(|room| room.entity)
+    }
+
+    /// Remember the item at the tile as collected
+    pub fn collect(&mut self, tile: TileId) {
+        self.collected.insert(tile);
+    }
+
     pub fn load_room(
         &mut self,
         asset_cache: &mut AssetCache<EmbeddedAssets>,
         assets: &mut Assets<Image>,
         commands: &mut Commands,
+        enemies: &EnemyRegistry,
+        abilities: &AbilityRegistry,
         load_room: LoadRoomConfig,
         spawn_direction: Option<ConnectionSide>,
     ) -> Result<Option<PlayerSpawnPoint>, LoadMapError> {
@@ -84,6 +120,7 @@ impl MapManager {
                 self.current_section = Section {
                     name: new_section,
                     colors: section.colors,
+                    gravity: section.gravity,
                 };
 
                 section_path
@@ -97,6 +134,7 @@ impl MapManager {
         };
 
         let room: RoomConfig = load_toml(section_path.join(&*load_room.room).join("room.toml"))?;
+        commands.insert_resource(SectionGravity(self.current_section.gravity));
 
         let variation_iter = load_room.variation.iter().flat_map(|id| {
             room.variations
@@ -122,7 +160,11 @@ impl MapManager {
                     Layer {
                         map: &self.map,
                         colors: &self.current_section.colors,
+                        enemies,
+                        abilities,
                         room: &room,
+                        room_id: (self.current_section.name.clone(), load_room.room.clone()),
+                        collected: &self.collected,
                         z_index: idx.wrapping_neg(),
                     },
                     room_parent,
@@ -157,6 +199,15 @@ impl MapManager {
     }
 }
 
+/// A tile of a room, staying the same when the room is loaded again
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TileId {
+    section: Cow<'static, str>,
+    room: Cow<'static, str>,
+    x: i16,
+    y: i16,
+}
+
 #[derive(Debug)]
 pub struct Room {
     #[allow(dead_code)] //TODO use this for the checkpoint room/going back one room
@@ -168,6 +219,7 @@ pub struct Room {
 pub struct Section {
     name: Cow<'static, str>,
     colors: Colors,
+    gravity: f32,
 }
 
 #[derive(Deserialize, Debug)]
@@ -184,16 +236,42 @@ pub struct TileConfig {
     #[serde(default)]
     breakable: bool,
     item: Option<AbilityItem>,
+    /// Turns the tile into an upgrade item of the ability
+    upgrade: Option<AbilityItem>,
     collision: Option<CollisionFilter>,
+    /// The collision layers of the collider, only the world layer by default
+    layers: Option<CollisionLayers>,
+    /// The collision layers the collider collides with, all layers by default
+    mask: Option<CollisionLayers>,
+    /// The surface material of the collider
+    material: Option<SurfaceMaterial>,
     #[serde(default)]
     connection: Option<ConnectionSide>,
     #[serde(default)]
     enemy: Option<EnemyKind>,
+    /// The spawn parameters of the enemy, the rooms can override them
+    #[serde(default)]
+    enemy_params: EnemyParams,
+    #[serde(default)]
+    combine: bool,
+    /// Turns the tile into a volume changing the physics of everything inside it
+    volume: Option<VolumeConfig>,
+    /// Turns the tile into a falling body colliding with the other bodies
+    body: Option<BodyConfig>,
+    /// Damages the player touching the tile
+    damage: Option<u32>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct SectionConfig {
     colors: Colors,
+    /// The gravity scale of all rooms in the section
+    #[serde(default = "default_section_gravity")]
+    gravity: f32,
+}
+
+fn default_section_gravity() -> f32 {
+    1.0
 }
 
 #[derive(Deserialize, Debug)]
@@ -205,6 +283,12 @@ pub struct RoomConfig {
     /// List of layers meant for collision
     #[serde(default)]
     collisions: HashMap<String, bool>,
+    /// Moving platforms by the color of their tiles
+    #[serde(default)]
+    platforms: HashMap<String, PlatformConfig>,
+    /// Spawn parameters of the enemies by the color of their tiles, overriding the ones of the tile
+    #[serde(default)]
+    enemies: HashMap<String, EnemyParams>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -224,16 +308,6 @@ pub enum ConnectionSide {
 }
 
 impl ConnectionSide {
-    pub fn matches_collision(self, coll_dir: &Collision) -> bool {
-        matches!(
-            (self, coll_dir),
-            (Self::Bottom, Collision::Top)
-                | (Self::Top, Collision::Bottom)
-                | (Self::Left, Collision::Right)
-                | (Self::Right, Collision::Left)
-        )
-    }
-
     pub fn inverse(self) -> Self {
         match self {
             Self::Bottom => Self::Top,
@@ -242,6 +316,16 @@ impl ConnectionSide {
             Self::Left => Self::Right,
         }
     }
+
+    /// The direction pointing out of the room through this side
+    pub fn direction(self) -> Vec2 {
+        match self {
+            Self::Top => Vec2::Y,
+            Self::Right => Vec2::X,
+            Self::Bottom => -Vec2::Y,
+            Self::Left => -Vec2::X,
+        }
+    }
 }
 
 fn load_collision_layer<P: AsRef<Path>>(
@@ -274,6 +358,9 @@ fn load_collision_layer<P: AsRef<Path>>(
                         .insert(Collider {
                             size,
                             filter: CollisionFilter::ALL,
+                            layers: CollisionLayers::WORLD,
+                            mask: CollisionLayers::ALL,
+                            material: SurfaceMaterial::default(),
                             collision_offset: Vec2::ZERO,
                         })
                         .id();
@@ -317,20 +404,37 @@ fn load_layer<P: AsRef<Path>>(
                     .get(sprite_id)
                     .ok_or_else(|| LoadMapError::InvalidSprite(sprite_id.clone()))?;
 
-                if let Some(enemy) = tile_config.enemy {
-                    let tile_id = match ENEMY_MAP.get(&enemy) {
-                        Some(enemy) => enemy.spawn_at(
-                            commands,
-                            assets,
-                            asset_cache,
-                            Vec3::new(f32::from(x) * TILE_SIZE, f32::from(y) * TILE_SIZE, 0.0),
-                        ),
-                        None => unimplemented!("Enemy {:?} is not yet implemented", enemy),
+                if let Some(kind) = &tile_config.enemy {
+                    let enemy = layer
+                        .enemies
+                        .get(kind)
+                        .ok_or_else(|| LoadMapError::UnknownEnemy(kind.clone()))?;
+                    let params = match layer.room.enemies.get(&color_hex) {
+                        Some(room_params) => tile_config.enemy_params.overridden_by(room_params),
+                        None => tile_config.enemy_params,
                     };
+                    let tile_id = enemy.spawn_at(
+                        commands,
+                        assets,
+                        asset_cache,
+                        Vec3::new(f32::from(x) * TILE_SIZE, f32::from(y) * TILE_SIZE, 0.0),
+                        &params,
+                    );
                     commands.entity(parent).add_child(tile_id);
                     continue;
                 }
 
+                for item in tile_config.item.iter().chain(&tile_config.upgrade) {
+                    if layer.abilities.get(item).is_none() {
+                        return Err(LoadMapError::UnknownAbility(item.clone()));
+                    }
+                }
+
+                // Collected items are gone for good
+                if tile_config.upgrade.is_some() && layer.collected.contains(&layer.tile_id(x, y)) {
+                    continue;
+                }
+
                 let mut tile = commands.spawn();
 
                 let translation = {
@@ -344,11 +448,10 @@ fn load_layer<P: AsRef<Path>>(
                         if let Some(connection_config) = layer.room.connections.get(&color_hex) {
                             println!("{:?}", connection_side);
                             tile.insert(Connection(connection_config.clone(), connection_side))
-                                .insert(Collider {
-                                    size: Vec2::splat(TILE_SIZE),
-                                    collision_offset: Vec2::ZERO,
-                                    filter: CollisionFilter::ALL,
-                                });
+                                .insert(
+                                    Trigger::new(Vec2::splat(TILE_SIZE), Vec3::ZERO)
+                                        .with_mask(CollisionLayers::PLAYER),
+                                );
 
                             let offset = match connection_side {
                                 ConnectionSide::Bottom => Vec3::new(0.0, -TILE_SIZE, 0.0),
@@ -412,20 +515,72 @@ fn load_layer<P: AsRef<Path>>(
                     tile.insert(Collider {
                         size,
                         filter,
+                        layers: tile_config.layers.unwrap_or(CollisionLayers::WORLD),
+                        mask: tile_config.mask.unwrap_or(CollisionLayers::ALL),
+                        material: tile_config.material.unwrap_or_default(),
                         collision_offset: Vec2::ZERO,
                     });
                 }
 
+                if let Some(body_config) = &tile_config.body {
+                    let mut vel_map = VelocityMap::new();
+                    tile.insert(Body::from_config_in(&mut vel_map, body_config))
+                        .insert(Gravity::new_in(&mut vel_map))
+                        .insert(PlatformRider::new_in(&mut vel_map))
+                        .insert(GroundContact::new_in(&mut vel_map))
+                        .insert(VolumeEffects::new_in(&mut vel_map))
+                        .insert(MoveableCollider)
+                        .insert(vel_map);
+                }
+
                 if tile_config.breakable {
                     tile.insert(BreakableCollider);
                 }
 
-                if let Some(item) = tile_config.item.and_then(|item| ABILITY_MAP.get(&item)) {
-                    tile.insert(CollectibleAbilityTrigger::new_with_descriptor(
-                        Vec2::new(32.0, 64.0),
-                        Vec3::ZERO,
-                        *item,
-                    ));
+                if let Some(platform_config) = layer.room.platforms.get(&color_hex) {
+                    let mut vel_map = VelocityMap::new();
+                    tile.insert(MovingPlatform::new_in(
+                        &mut vel_map,
+                        translation.truncate(),
+                        platform_config,
+                    ))
+                    .insert(vel_map);
+                }
+
+                if let Some(item) = &tile_config.item {
+                    tile.insert(CollectibleAbilityTrigger::new(item.clone()))
+                        .insert(
+                            Trigger::new(Vec2::new(32.0, 64.0), Vec3::ZERO)
+                                .with_mask(CollisionLayers::PLAYER),
+                        );
+                }
+
+                if let Some(item) = &tile_config.upgrade {
+                    tile.insert(CollectibleUpgradeTrigger::new(
+                        item.clone(),
+                        layer.tile_id(x, y),
+                    ))
+                    .insert(Trigger::new(size, Vec3::ZERO).with_mask(CollisionLayers::PLAYER));
+                }
+
+                if tile_config.combine {
+                    tile.insert(CombineAltar).insert(
+                        Trigger::new(Vec2::new(32.0, 64.0), Vec3::ZERO)
+                            .with_mask(CollisionLayers::PLAYER),
+                    );
+                }
+
+                if let Some(volume_config) = &tile_config.volume {
+                    // The player shots fly straight through water and wind
+                    tile.insert(Volume::new(volume_config)).insert(
+                        Trigger::new(size, Vec3::ZERO)
+                            .with_mask(!CollisionLayers::PLAYER_PROJECTILE),
+                    );
+                }
+
+                if let Some(damage) = tile_config.damage {
+                    tile.insert(ContactDamage::new(damage))
+                        .insert(Trigger::new(size, Vec3::ZERO));
                 }
                 let tile_id = tile.id();
                 commands.entity(parent).add_child(tile_id);
@@ -441,13 +596,29 @@ pub struct PlayerSpawnPoint {
     pub spawn_point: Vec3,
 }
 
-struct Layer<'room, 'map, 'colors> {
+struct Layer<'room, 'map, 'colors, 'registry> {
     room: &'room RoomConfig,
+    /// The section and room names
+    room_id: (Cow<'static, str>, Cow<'static, str>),
+    collected: &'map HashSet<TileId>,
     map: &'map Map,
     colors: &'colors Colors,
+    enemies: &'registry EnemyRegistry,
+    abilities: &'registry AbilityRegistry,
     z_index: i16,
 }
 
+impl Layer<'_, '_, '_, '_> {
+    fn tile_id(&self, x: i16, y: i16) -> TileId {
+        TileId {
+            section: self.room_id.0.clone(),
+            room: self.room_id.1.clone(),
+            x,
+            y,
+        }
+    }
+}
+
 fn load_toml<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, LoadMapError> {
     let data = EmbeddedData::load(path)?;
     toml::from_slice(&data).map_err(Into::into)
@@ -465,4 +636,8 @@ pub enum LoadMapError {
     InvalidSprite(String),
     #[error("The given section could not be found: {0}")]
     SectionNotFoundError(Cow<'static, str>),
+    #[error("The enemy is not registered: {0}")]
+    UnknownEnemy(EnemyKind),
+    #[error("The ability is not registered: {0}")]
+    UnknownAbility(AbilityItem),
 }
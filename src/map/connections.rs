@@ -3,32 +3,44 @@ use bevy::prelude::*;
 use super::{ConnectionSide, LoadRoomConfig, MapManager};
 use crate::{
     asset_loaders::{cache::AssetCache, EmbeddedAssets},
-    collision::CollisionEvent,
-    player::PlayerSpawn,
+    collision::triggers::{TriggerEvent, TriggerEventKind},
+    enemies::EnemyRegistry,
+    physics::VelocityMap,
+    player::{abilities::registry::AbilityRegistry, PlayerSpawn},
 };
 
 #[derive(Component, Debug)]
 pub struct Connection(pub(super) LoadRoomConfig, pub(super) ConnectionSide);
 
-pub fn connection_collision_system(
+pub fn connection_trigger_system(
     mut commands: Commands,
-    mut collision_reader: EventReader<CollisionEvent>,
+    mut trigger_reader: EventReader<TriggerEvent>,
     connections_query: Query<&Connection>,
-    mut player_query: Query<(&mut Transform, &mut PlayerSpawn)>,
+    mut player_query: Query<(&mut Transform, &mut PlayerSpawn, &mut VelocityMap)>,
     mut asset_cache: ResMut<AssetCache<EmbeddedAssets>>,
     mut assets: ResMut<Assets<Image>>,
     mut map: ResMut<MapManager>,
+    enemies: Res<EnemyRegistry>,
+    abilities: Res<AbilityRegistry>,
 ) {
-    for collision in collision_reader.iter() {
-        if let Ok(connection) = connections_query.get(collision.static_entity) {
-            if !connection.1.matches_collision(&collision.collision) {
-                continue;
-            }
+    // A connection is wider than one tile, the other tiles belong to the old room
+    let mut room_changed = false;
+
+    for evt in trigger_reader.iter() {
+        if room_changed || evt.kind != TriggerEventKind::Enter {
+            continue;
+        }
 
-            // dbg!(&collision);
-            if let Ok((mut player_trans, mut player_spawn)) =
-                player_query.get_mut(collision.moving_entity)
+        if let Ok(connection) = connections_query.get(evt.trigger) {
+            if let Ok((mut player_trans, mut player_spawn, mut vel_map)) =
+                player_query.get_mut(evt.entity)
             {
+                // Only leave the room by moving out through the side of the connection, not by
+                // e.g. spawning inside it or jumping up through a connection on the bottom
+                if vel_map.last_velocity().dot(connection.1.direction()) <= 0.0 {
+                    continue;
+                }
+
                 if let Some(room) = map.room_stack.pop() {
                     commands.entity(room.entity).despawn_recursive();
                 }
@@ -37,6 +49,8 @@ pub fn connection_collision_system(
                         &mut asset_cache,
                         &mut assets,
                         &mut commands,
+                        &enemies,
+                        &abilities,
                         connection.0.clone(), // TODO this clone could be eliminated with more 'static
                         Some(connection.1.inverse()),
                     )
@@ -44,10 +58,13 @@ pub fn connection_collision_system(
                 if let Some(spawn_point) = spawn_point {
                     dbg!(&spawn_point);
                     player_trans.translation = spawn_point.spawn_point;
+                    player_spawn.respawn_point = spawn_point.spawn_point;
                     if spawn_point.spawn_dir == ConnectionSide::Bottom {
-                        player_spawn.spawn_from_bottom();
+                        player_spawn.spawn_from_bottom(&mut vel_map);
                     }
                 }
+
+                room_changed = true;
             }
         }
     }
@@ -0,0 +1,142 @@
+//! Platforms moving along waypoints and carrying everything standing on them
+
+use bevy::{prelude::*, sprite::collide_aabb::Collision};
+use serde::Deserialize;
+
+use super::TILE_SIZE;
+use crate::{
+    collision::CollisionEvent,
+    physics::{VelocityId, VelocityMap},
+};
+
+/// The configuration of a moving platform inside a `room.toml`, referenced by the tile color
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlatformConfig {
+    /// The waypoints in tiles, relative to the position of the platform tile
+    waypoints: Vec<[i16; 2]>,
+    /// The distance moved per frame
+    #[serde(default = "default_platform_speed")]
+    speed: f32,
+}
+
+fn default_platform_speed() -> f32 {
+    1.0
+}
+
+/// A platform moving along its waypoints, starting again at the first one after the last one
+#[derive(Component, Debug)]
+pub struct MovingPlatform {
+    waypoints: Vec<Vec2>,
+    next_waypoint: usize,
+    speed: f32,
+    vel_id: VelocityId,
+    /// The velocity of the platform in this frame
+    velocity: Vec2,
+}
+
+impl MovingPlatform {
+    /// Create a new moving platform placed at `origin` inside the given [`VelocityMap`]
+    pub fn new_in(vel_map: &mut VelocityMap, origin: Vec2, config: &PlatformConfig) -> Self {
+        let mut waypoints: Vec<Vec2> = config
+            .waypoints
+            .iter()
+            .map(|[x, y]| origin + Vec2::new(f32::from(*x), f32::from(*y)) * TILE_SIZE)
+            .collect();
+
+        if waypoints.is_empty() {
+            waypoints.push(origin);
+        }
+
+        Self {
+            waypoints,
+            next_waypoint: 0,
+            speed: config.speed,
+            vel_id: vel_map.register().0,
+            velocity: Vec2::ZERO,
+        }
+    }
+}
+
+/// Moves the platforms towards their next waypoint
+pub fn moving_platform_system(
+    mut query: Query<(&Transform, &mut MovingPlatform, &mut VelocityMap)>,
+) {
+    for (transform, mut platform, mut vel_map) in query.iter_mut() {
+        let to_waypoint =
+            platform.waypoints[platform.next_waypoint] - transform.translation.truncate();
+
+        platform.velocity = if to_waypoint.length() <= platform.speed {
+            platform.next_waypoint = (platform.next_waypoint + 1) % platform.waypoints.len();
+            to_waypoint
+        } else {
+            to_waypoint.normalize() * platform.speed
+        };
+
+        if let Err(e) = vel_map.set(platform.vel_id, platform.velocity) {
+            panic!(
+                "{} -> Platform velocity not inside map, you forgot to register it",
+                e
+            );
+        }
+    }
+}
+
+/// A moveable collider which is carried by the moving platform it stands on
+#[derive(Component, Debug)]
+pub struct PlatformRider {
+    vel_id: VelocityId,
+    platform: Option<Entity>,
+}
+
+impl PlatformRider {
+    /// Create a new platform rider inside the given [`VelocityMap`]
+    pub fn new_in(vel_map: &mut VelocityMap) -> Self {
+        Self {
+            vel_id: vel_map.register().0,
+            platform: None,
+        }
+    }
+}
+
+/// Remembers the moving platforms the riders landed on this frame
+pub fn platform_rider_system(
+    mut collision_reader: EventReader<CollisionEvent>,
+    platform_query: Query<&MovingPlatform>,
+    mut rider_query: Query<&mut PlatformRider>,
+) {
+    for mut rider in rider_query.iter_mut() {
+        rider.platform = None;
+    }
+
+    for evt in collision_reader.iter() {
+        if !matches!(evt.collision, Collision::Top)
+            || platform_query.get(evt.static_entity).is_err()
+        {
+            continue;
+        }
+
+        if let Ok(mut rider) = rider_query.get_mut(evt.moving_entity) {
+            rider.platform = Some(evt.static_entity);
+        }
+    }
+}
+
+/// Moves the riders along with the platform they are standing on
+pub fn platform_carry_system(
+    platform_query: Query<&MovingPlatform>,
+    mut rider_query: Query<(&PlatformRider, &mut VelocityMap)>,
+) {
+    for (rider, mut vel_map) in rider_query.iter_mut() {
+        let velocity = rider
+            .platform
+            .and_then(|platform| platform_query.get(platform).ok())
+            .map_or(Vec2::ZERO, |platform| platform.velocity);
+
+        if let Err(e) = vel_map.set(rider.vel_id, velocity) {
+            panic!(
+                "{} -> Rider velocity not inside map, you forgot to register it",
+                e
+            );
+        }
+    }
+}
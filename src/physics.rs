@@ -1,8 +1,14 @@
-use bevy::{prelude::*, sprite::collide_aabb::Collision};
+pub mod volumes;
+
+use std::time::Duration;
+
+use bevy::{prelude::*, sprite::collide_aabb::Collision, utils::Instant};
 
 use crate::{
-    collision::{CollisionEvent, MoveableCollider},
-    LATE_UPDATE_STAGE,
+    collision::{
+        bodies::BodyCollisionLabel, Collider, CollisionEvent, MoveableCollider, SurfaceMaterial,
+    },
+    LATE_UPDATE_STAGE, POST_COLLISION_STAGE,
 };
 
 pub const VEL_SYSTEM_STAGE: &str = "vel_sys";
@@ -15,11 +21,17 @@ pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(gravity_system)
+        app.init_resource::<SectionGravity>()
+            .add_system(gravity_system)
+            .add_system(volumes::volume_system)
             .add_stage_after(LATE_UPDATE_STAGE, VEL_SYSTEM_STAGE, SystemStage::parallel())
             .add_stage_after(VEL_SYSTEM_STAGE, VEL_MOVE_STAGE, SystemStage::parallel())
             .add_system_to_stage(VEL_SYSTEM_STAGE, add_gravity_velocity_system)
             .add_system_to_stage(LATE_UPDATE_STAGE, landing_system)
+            .add_system_to_stage(
+                POST_COLLISION_STAGE,
+                ground_contact_system.after(BodyCollisionLabel),
+            )
             .add_system_to_stage(VEL_MOVE_STAGE, velocity_system);
     }
 }
@@ -28,13 +40,63 @@ impl Plugin for PhysicsPlugin {
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub struct VelocityId(usize);
 
+/// An id to an impulse inside a velocity map
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct ImpulseId(usize);
+
+/// How the velocity of an impulse decreases over its duration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decay {
+    /// The full velocity until the impulse expires
+    None,
+    /// Linearly decreasing to zero
+    Linear,
+    /// Fast decreasing at the start, slow at the end
+    EaseOut,
+}
+
+impl Decay {
+    /// The factor of the velocity left at `progress` (from 0 to 1) of the duration
+    fn factor(self, progress: f32) -> f32 {
+        match self {
+            Self::None => 1.0,
+            Self::Linear => 1.0 - progress,
+            Self::EaseOut => (1.0 - progress).powi(2),
+        }
+    }
+}
+
+/// A velocity which is only applied for a limited time
+#[derive(Debug, Clone)]
+struct Impulse {
+    id: ImpulseId,
+    velocity: Vec2,
+    decay: Decay,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Impulse {
+    fn is_active(&self) -> bool {
+        self.start.elapsed() < self.duration
+    }
+
+    fn current_velocity(&self) -> Vec2 {
+        let progress = self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32();
+        self.velocity * self.decay.factor(progress.min(1.0))
+    }
+}
+
 /// A map of velocities set by different component.
 ///
 /// Every component can add its own velocity to this map to be applied after the update stage.
+/// Impulses can be added to push an entity for some time, they are removed after they expired.
 #[derive(Component, Debug, Default, Clone)]
 pub struct VelocityMap {
     /// The backing storage of all velocities
     map: Vec<Vec2>,
+    impulses: Vec<Impulse>,
+    next_impulse_id: usize,
     last_velocity: Vec2,
 }
 
@@ -77,6 +139,33 @@ impl VelocityMap {
             Err(VelocityError::NotFound)
         }
     }
+
+    /// Add an impulse starting with `velocity`, decreasing by `decay` until `duration` passed
+    pub fn add_impulse(&mut self, velocity: Vec2, duration: Duration, decay: Decay) -> ImpulseId {
+        let id = ImpulseId(self.next_impulse_id);
+        self.next_impulse_id += 1;
+        self.impulses.push(Impulse {
+            id,
+            velocity,
+            decay,
+            start: Instant::now(),
+            duration,
+        });
+
+        id
+    }
+
+    /// Add a constant velocity which is applied until `duration` passed
+    pub fn add_timed(&mut self, velocity: Vec2, duration: Duration) -> ImpulseId {
+        self.add_impulse(velocity, duration, Decay::None)
+    }
+
+    /// Whether the impulse with the given id has not yet expired
+    pub fn is_active(&self, id: ImpulseId) -> bool {
+        self.impulses
+            .iter()
+            .any(|impulse| impulse.id == id && impulse.is_active())
+    }
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -87,18 +176,35 @@ pub enum VelocityError {
 
 pub fn velocity_system(mut query: Query<(&mut Transform, &mut VelocityMap)>) {
     for (mut transform, mut velocity_map) in query.iter_mut() {
-        let velocity: Vec2 = velocity_map.map.iter().sum();
+        velocity_map.impulses.retain(Impulse::is_active);
+        let velocity = velocity_map.map.iter().sum::<Vec2>()
+            + velocity_map
+                .impulses
+                .iter()
+                .fold(Vec2::ZERO, |acc, impulse| acc + impulse.current_velocity());
         let z = transform.translation.z;
         transform.translation += velocity.extend(z);
         velocity_map.last_velocity = velocity;
     }
 }
 
+/// The gravity scale of the current section, set by the `gravity` of its `section.toml`
+#[derive(Debug, Clone, Copy)]
+pub struct SectionGravity(pub f32);
+
+impl Default for SectionGravity {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
 /// Gravity component to make things fall
 #[derive(Component, Debug)]
 pub struct Gravity {
     pub(crate) vel_id: VelocityId,
     pub velocity: Vec2,
+    /// The scale of the gravity, on top of the [`SectionGravity`]
+    pub scale: f32,
 }
 
 impl Gravity {
@@ -107,6 +213,7 @@ impl Gravity {
         Self {
             vel_id,
             velocity: Vec2::ZERO,
+            scale: 1.0,
         }
     }
 
@@ -116,16 +223,20 @@ impl Gravity {
 }
 
 /// System to apply gravity to all entities with the Gravity components
-pub fn gravity_system(mut query: Query<&mut Gravity>) {
+pub fn gravity_system(mut query: Query<&mut Gravity>, section_gravity: Res<SectionGravity>) {
     for mut grav in query.iter_mut() {
-        grav.velocity.y = (grav.velocity.y - GRAVITY).max(GRAVITY_MAX);
+        let scale = grav.scale * section_gravity.0;
+        grav.velocity.y = (grav.velocity.y - GRAVITY * scale).max(GRAVITY_MAX * scale);
     }
 }
 
 pub fn landing_system(
     mut collision_event_reader: EventReader<CollisionEvent>,
+    collider_query: Query<&Collider>,
     mut query: Query<&mut Gravity, With<MoveableCollider>>,
 ) {
+    const MIN_BOUNCE_SPEED: f32 = GRAVITY * 3.0;
+
     for evt in collision_event_reader.iter() {
         match evt.collision {
             Collision::Top => (),
@@ -133,7 +244,94 @@ pub fn landing_system(
         }
 
         if let Ok(mut grav) = query.get_mut(evt.moving_entity) {
-            grav.velocity = Vec2::ZERO;
+            // Already bounced off another collider
+            if grav.velocity.y > 0.0 {
+                continue;
+            }
+
+            let restitution = collider_query
+                .get(evt.static_entity)
+                .map_or(0.0, |collider| collider.material.restitution);
+            let bounce_speed = -grav.velocity.y * restitution;
+
+            grav.velocity = if bounce_speed > MIN_BOUNCE_SPEED {
+                Vec2::new(0.0, bounce_speed)
+            } else {
+                Vec2::ZERO
+            };
+        }
+    }
+}
+
+/// The surface a moveable collider is standing on
+#[derive(Component, Debug)]
+pub struct GroundContact {
+    /// The velocity of conveyor surfaces
+    vel_id: VelocityId,
+    /// The material of the ground, `None` while airborne
+    material: Option<SurfaceMaterial>,
+}
+
+impl GroundContact {
+    /// Create a new ground contact inside the given [`VelocityMap`]
+    pub fn new_in(vel_map: &mut VelocityMap) -> Self {
+        Self {
+            vel_id: vel_map.register().0,
+            material: None,
+        }
+    }
+
+    /// Whether the entity is standing on something
+    pub fn is_grounded(&self) -> bool {
+        self.material.is_some()
+    }
+
+    /// The friction of the ground, 1 in the air
+    pub fn friction(&self) -> f32 {
+        self.material
+            .map_or(1.0, |material| material.friction.clamp(0.0, 1.0))
+    }
+
+    /// Move the horizontal velocity `current` towards `target` according to the friction of the
+    /// ground, in the air the velocity changes instantly
+    pub fn approach(&self, current: f32, target: f32) -> f32 {
+        current + (target - current) * self.friction()
+    }
+}
+
+/// Remembers the surfaces the moveable colliders are standing on and applies conveyors
+pub fn ground_contact_system(
+    mut collision_event_reader: EventReader<CollisionEvent>,
+    collider_query: Query<&Collider>,
+    mut query: Query<(&mut GroundContact, &mut VelocityMap)>,
+) {
+    for (mut ground, _) in query.iter_mut() {
+        ground.material = None;
+    }
+
+    for evt in collision_event_reader.iter() {
+        if !matches!(evt.collision, Collision::Top) {
+            continue;
+        }
+
+        if let (Ok((mut ground, _)), Ok(collider)) = (
+            query.get_mut(evt.moving_entity),
+            collider_query.get(evt.static_entity),
+        ) {
+            ground.material = Some(collider.material);
+        }
+    }
+
+    for (ground, mut vel_map) in query.iter_mut() {
+        let conveyor_speed = ground
+            .material
+            .map_or(0.0, |material| material.conveyor_speed);
+
+        if let Err(e) = vel_map.set(ground.vel_id, Vec2::new(conveyor_speed, 0.0)) {
+            panic!(
+                "{} -> Conveyor velocity not inside map, you forgot to register it",
+                e
+            );
         }
     }
 }
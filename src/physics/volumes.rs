@@ -0,0 +1,116 @@
+//! Areas changing the physics of everything inside of them, like water, wind or low gravity
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use super::{Gravity, VelocityId, VelocityMap};
+use crate::collision::triggers::TriggerEvent;
+
+/// The configuration of a volume tile inside the `main.toml`
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct VolumeConfig {
+    /// The scale of the gravity inside the volume
+    gravity_scale: f32,
+    /// The constant velocity everything inside the volume is moved with, on top of its own
+    velocity: [f32; 2],
+    /// Whether the player swims inside the volume
+    swim: bool,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        Self {
+            gravity_scale: 1.0,
+            velocity: [0.0; 2],
+            swim: false,
+        }
+    }
+}
+
+/// An area changing the physics of the moveable colliders inside its trigger
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Volume {
+    gravity_scale: f32,
+    velocity: Vec2,
+    swim: bool,
+}
+
+impl Volume {
+    pub fn new(config: &VolumeConfig) -> Self {
+        Self {
+            gravity_scale: config.gravity_scale,
+            velocity: Vec2::from(config.velocity),
+            swim: config.swim,
+        }
+    }
+}
+
+/// The effects of all volumes an entity is inside of.
+///
+/// Overlapping volumes do not stack, so a volume made of many tiles acts like a single one:
+/// the lowest gravity scale and the strongest velocity win.
+#[derive(Component, Debug)]
+pub struct VolumeEffects {
+    /// The velocity of the volumes
+    vel_id: VelocityId,
+    swimming: bool,
+}
+
+impl VolumeEffects {
+    /// Create new volume effects inside the given [`VelocityMap`]
+    pub fn new_in(vel_map: &mut VelocityMap) -> Self {
+        Self {
+            vel_id: vel_map.register().0,
+            swimming: false,
+        }
+    }
+
+    /// Whether the entity is inside a volume to swim in
+    pub fn is_swimming(&self) -> bool {
+        self.swimming
+    }
+}
+
+/// Applies the volumes to every entity inside of them
+pub fn volume_system(
+    mut trigger_reader: EventReader<TriggerEvent>,
+    volume_query: Query<&Volume>,
+    mut query: Query<(&mut VolumeEffects, &mut Gravity, &mut VelocityMap, Entity)>,
+) {
+    let mut combined: HashMap<Entity, Volume> = HashMap::default();
+
+    for evt in trigger_reader.iter() {
+        if !evt.is_inside() {
+            continue;
+        }
+
+        if let Ok(volume) = volume_query.get(evt.trigger) {
+            combined
+                .entry(evt.entity)
+                .and_modify(|combined| {
+                    combined.gravity_scale = combined.gravity_scale.min(volume.gravity_scale);
+                    if volume.velocity.length_squared() > combined.velocity.length_squared() {
+                        combined.velocity = volume.velocity;
+                    }
+                    combined.swim |= volume.swim;
+                })
+                .or_insert(*volume);
+        }
+    }
+
+    for (mut effects, mut grav, mut vel_map, entity) in query.iter_mut() {
+        let volume = combined.get(&entity);
+
+        grav.scale = volume.map_or(1.0, |volume| volume.gravity_scale);
+        effects.swimming = volume.map_or(false, |volume| volume.swim);
+
+        let velocity = volume.map_or(Vec2::ZERO, |volume| volume.velocity);
+        if let Err(e) = vel_map.set(effects.vel_id, velocity) {
+            panic!(
+                "{} -> Volume velocity not inside map, you forgot to register it",
+                e
+            );
+        }
+    }
+}
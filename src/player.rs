@@ -1,27 +1,40 @@
 pub mod abilities;
+pub mod animations;
+pub mod config;
+pub mod health;
+pub mod ledge;
+pub mod state;
 
-use std::any::TypeId;
 use std::time::Duration;
 
-use bevy::utils::{HashSet, Instant};
 use bevy::{prelude::*, sprite::collide_aabb::Collision};
 
+use crate::animation::{self, Animator};
 use crate::asset_loaders::cache::AssetCache;
-use crate::collision::{Collider, CollisionFilter};
-use crate::POST_COLLISION_STAGE;
+use crate::collision::{bodies::Body, Collider, CollisionFilter, CollisionLayers, SurfaceMaterial};
+use crate::controls::{self, Action, Aim};
 use crate::{
     asset_loaders::EmbeddedAssets,
     camera::FollowedByCamera,
     collision::{CollisionEvent, MoveableCollider},
-    physics::{Gravity, VelocityId, VelocityMap, GRAVITY, VEL_SYSTEM_STAGE},
+    map::platforms::PlatformRider,
+    physics::{
+        volumes::VolumeEffects, Decay, Gravity, GroundContact, ImpulseId, SectionGravity,
+        VelocityId, VelocityMap, GRAVITY, VEL_SYSTEM_STAGE,
+    },
+    util::{move_towards, TimeWindow},
     LATE_UPDATE_STAGE, PLAYER_SIZE,
 };
-use abilities::{collectibles, PlayerInventory};
-
-use self::abilities::{
-    crouch_collision_system, double_jump_land_system, player_crouch_system,
-    player_wall_jump_system, wall_jump_collision_system, PlayerDash, PlayerShoot, PlayerWallJump,
+use abilities::{
+    collectibles, input::AbilityInput, registry::AbilityRegistry, upgrades, PlayerInventory,
 };
+use animations::PlayerAnimations;
+use config::PlayerConfig;
+use health::{PlayerDeathEvent, PlayerHealth};
+use ledge::PlayerLedgeGrab;
+use state::{PlayerStateEvent, PlayerStateMachine};
+
+use self::abilities::{PlayerDash, PlayerShoot, PlayerWallJump};
 
 pub const PLAYER_SPAWN_STAGE: &str = "play_spawn";
 
@@ -37,26 +50,45 @@ impl Plugin for PlayerPlugin {
         )
         .add_startup_system(player_setup_system)
         .add_system(player_input_system)
+        .add_system(health::player_damage_system)
+        .add_system(health::player_stun_system.after(health::player_damage_system))
+        .add_system(health::player_flash_system)
+        .add_system(player_respawn_system)
+        .add_system(state::player_ground_state_system)
         .add_system(player_jump_system)
         .add_system(player_collision_system)
-        .add_system(abilities::player_shoot_system)
         .add_system(collectibles::collect_ability_system)
-        .add_system(abilities::player_shot_collision_system)
+        .add_system(collectibles::combine_altar_system)
+        .add_system(upgrades::collect_upgrade_system)
         .add_system(player_turn_system)
-        .add_system_to_stage(CoreStage::PreUpdate, move_cursor_system)
-        .add_system_to_stage(LATE_UPDATE_STAGE, abilities::player_dash_system)
-        .add_system_to_stage(LATE_UPDATE_STAGE, abilities::player_double_jump_system)
-        .add_system_to_stage(POST_COLLISION_STAGE, double_jump_land_system)
-        .add_system_to_stage(LATE_UPDATE_STAGE, player_crouch_system)
-        .add_system_to_stage(POST_COLLISION_STAGE, crouch_collision_system)
-        .add_system_to_stage(POST_COLLISION_STAGE, wall_jump_collision_system)
-        .add_system_to_stage(LATE_UPDATE_STAGE, player_wall_jump_system)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            move_cursor_system.after(controls::aim_system),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            abilities::input::ability_input_system.after(controls::action_input_system),
+        )
+        .add_system_to_stage(LATE_UPDATE_STAGE, ledge::player_ledge_grab_system)
+        .add_system_to_stage(
+            LATE_UPDATE_STAGE,
+            ledge::player_ledge_hang_system.after(ledge::player_ledge_grab_system),
+        )
         .add_system_to_stage(LATE_UPDATE_STAGE, player_fall_system)
         .add_system_to_stage(VEL_SYSTEM_STAGE, add_player_velocity_system)
         .add_system_to_stage(LATE_UPDATE_STAGE, player_spawn_help_system)
+        .add_system_to_stage(CoreStage::Last, state::player_state_event_system)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            animations::player_animation_system.before(animation::animation_system),
+        )
+        .init_resource::<PlayerConfig>()
+        .init_resource::<AbilityRegistry>()
         .add_event::<PlayerLandEvent>()
         .add_event::<PlayerCollisionEvent>()
-        .add_event::<JumpEvent>();
+        .add_event::<JumpEvent>()
+        .add_event::<PlayerStateEvent>()
+        .add_event::<PlayerDeathEvent>();
     }
 }
 
@@ -64,10 +96,15 @@ fn player_setup_system(
     mut commands: Commands,
     mut asset_cache: ResMut<AssetCache<EmbeddedAssets>>,
     mut assets: ResMut<Assets<Image>>,
+    config: Res<PlayerConfig>,
 ) {
-    let texture = asset_cache
-        .load_image(&mut assets, "sprites/character/movement/idle.png")
-        .unwrap_or_else(|e| panic!("The player sprite could not be loaded: {}", e));
+    let animations = PlayerAnimations::load(&mut asset_cache, &mut assets);
+    let animator = Animator::new(animations.idle.clone());
+    let texture = animator.texture();
+
+    let translation = Vec3::new(10.0 * PLAYER_SIZE, 4.0 * PLAYER_SIZE, 0.0);
+    // let translation = Vec3::new(84.0, 197.0, 0.0);
+    // let translation = Vec3::new(404.0, 12.0, 0.0);
 
     let mut vel_map = VelocityMap::new();
     commands
@@ -78,19 +115,28 @@ fn player_setup_system(
             },
             texture,
             transform: Transform {
-                translation: Vec3::new(10.0 * PLAYER_SIZE, 4.0 * PLAYER_SIZE, 0.0),
-                // translation: Vec3::new(84.0, 197.0, 0.0),
-                // translation: Vec3::new(404.0, 12.0, 0.0),
+                translation,
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert(FollowedByCamera)
+        .insert(animator)
+        .insert(animations)
         .insert(PlayerMovement::new_in(&mut vel_map))
+        .insert(PlayerStateMachine::default())
         .insert(Gravity::new_in(&mut vel_map))
-        .insert(PlayerSpawn::new(&mut vel_map))
+        .insert(PlayerSpawn::new(translation))
+        .insert(PlayerHealth::new(config.health.max))
+        .insert(PlayerLedgeGrab::default())
+        .insert(PlatformRider::new_in(&mut vel_map))
+        .insert(GroundContact::new_in(&mut vel_map))
+        .insert(VolumeEffects::new_in(&mut vel_map))
+        .insert(Body::new_in(&mut vel_map, 1.0, false))
         .insert(vel_map)
         .insert(PlayerInventory::new())
+        .insert(AbilityInput::default())
+        .insert(upgrades::AbilityUpgrades::default())
         .insert(PlayerInventory::new_with::<PlayerShoot, PlayerWallJump>())
         // .insert(PlayerCrouch::default())
         // .insert(PlayerDoubleJump::default())
@@ -101,6 +147,9 @@ fn player_setup_system(
             size: Vec2::new(PLAYER_SIZE / 1.2, PLAYER_SIZE),
             collision_offset: Vec2::new(PLAYER_SIZE / 8.5, PLAYER_SIZE / 3.0),
             filter: CollisionFilter::ALL,
+            layers: CollisionLayers::PLAYER,
+            mask: CollisionLayers::ALL,
+            material: SurfaceMaterial::default(),
         })
         .insert(MoveableCollider);
 }
@@ -112,8 +161,16 @@ pub struct JumpEvent(pub Entity);
 #[derive(Component, Debug)]
 pub struct PlayerMovement {
     vel_id: VelocityId,
-    can_jump: bool,
-    move_forbid_set: HashSet<TypeId>,
+    /// When the player was standing on the ground
+    grounded: TimeWindow,
+    /// When the player wanted to jump
+    jump_buffer: TimeWindow,
+    /// Whether the player is in a jump which is cut when releasing the jump key
+    jumping: bool,
+    /// Whether the jump key is ignored until it is released, e.g. after climbing up a ledge with it
+    jump_blocked: bool,
+    /// The last stroke while swimming, a new one can be started after it ended
+    swim_stroke: Option<ImpulseId>,
     pub velocity: Vec2,
 }
 
@@ -122,8 +179,11 @@ impl PlayerMovement {
     pub fn new(vel_id: VelocityId) -> Self {
         Self {
             vel_id,
-            can_jump: true,
-            move_forbid_set: HashSet::default(),
+            grounded: TimeWindow::default(),
+            jump_buffer: TimeWindow::default(),
+            jumping: false,
+            jump_blocked: false,
+            swim_stroke: None,
             velocity: Vec2::ZERO,
         }
     }
@@ -136,30 +196,68 @@ impl PlayerMovement {
 
 /// System to move the player with input
 pub fn player_input_system(
-    mut player_query: Query<(&mut PlayerMovement, Entity)>,
+    mut player_query: Query<(
+        &mut PlayerMovement,
+        &PlayerStateMachine,
+        &GroundContact,
+        &VolumeEffects,
+        Entity,
+    )>,
     mut jump_event_writer: EventWriter<JumpEvent>,
-    kb_input: ResMut<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
+    config: Res<PlayerConfig>,
 ) {
-    const SPEED: f32 = 3.5;
+    let movement = &config.movement;
+
+    for (mut player, state_machine, ground, volumes, entity) in player_query.iter_mut() {
+        let speed = if volumes.is_swimming() {
+            movement.swim_speed
+        } else {
+            movement.speed
+        };
+
+        // Releasing the jump key early makes the jump lower, the jump is over at its highest point
+        // so later upwards velocity (e.g. from a bounce pad) is never cut
+        if player.jumping && player.velocity.y <= 0.0 {
+            player.jumping = false;
+        }
+        if player.jumping && !actions.pressed(Action::Jump) {
+            player.jumping = false;
+            player.velocity.y *= config.jump.cut_multiplier;
+        }
 
-    for (mut player, entity) in player_query.iter_mut() {
-        if !player.move_forbid_set.is_empty() {
+        if player.jump_blocked && !actions.pressed(Action::Jump) {
+            player.jump_blocked = false;
+        }
+
+        if !state_machine.state().has_control() {
             continue;
         }
 
-        player.velocity.x = 0.0;
-        for key in kb_input.get_pressed() {
-            match key {
-                KeyCode::A => {
-                    player.velocity.x += -SPEED;
+        let mut target_x = 0.0;
+        for action in actions.get_pressed() {
+            match action {
+                Action::MoveLeft => {
+                    target_x += -speed;
                 }
-                KeyCode::D => {
-                    player.velocity.x += SPEED;
+                Action::MoveRight => {
+                    target_x += speed;
                 }
-                KeyCode::Space => jump_event_writer.send(JumpEvent(entity)),
+                Action::Jump if !player.jump_blocked => jump_event_writer.send(JumpEvent(entity)),
                 _ => (),
             }
         }
+
+        let accelerating = target_x != 0.0
+            && (player.velocity.x == 0.0 || target_x.signum() == player.velocity.x.signum());
+        let rate = match (ground.is_grounded(), accelerating) {
+            (true, true) => movement.acceleration,
+            (true, false) => movement.deceleration,
+            (false, true) => movement.air_acceleration,
+            (false, false) => movement.air_deceleration,
+        };
+
+        player.velocity.x = move_towards(player.velocity.x, target_x, rate * ground.friction());
     }
 }
 
@@ -179,21 +277,53 @@ fn player_turn_system(
 }
 
 pub fn player_jump_system(
-    mut player_query: Query<(&mut PlayerMovement, &mut Gravity)>,
+    mut player_query: Query<(
+        &mut PlayerMovement,
+        &PlayerStateMachine,
+        &mut Gravity,
+        &mut VelocityMap,
+        &VolumeEffects,
+    )>,
     mut jump_event_reader: EventReader<JumpEvent>,
+    config: Res<PlayerConfig>,
 ) {
-    const JUMP_POWER: f32 = 9.0;
+    const SWIM_STROKE_VEL: Vec2 = Vec2::new(0.0, 4.0);
+    const SWIM_STROKE_DUR: Duration = Duration::from_millis(500);
 
     for JumpEvent(entity) in jump_event_reader.iter() {
-        if let Ok((mut player_movement, mut grav)) = player_query.get_mut(*entity) {
-            let falling = is_falling(grav.velocity.y);
-            let can_jump = !falling && player_movement.can_jump;
-
-            if can_jump {
-                player_movement.velocity.y = JUMP_POWER;
-                player_movement.can_jump = false;
-                grav.velocity.y = 0.0;
+        if let Ok((mut player_movement, _, mut grav, mut vel_map, volumes)) =
+            player_query.get_mut(*entity)
+        {
+            // Swimming up with strokes instead of jumping, as long as the space key is held
+            if volumes.is_swimming() {
+                let stroking = player_movement
+                    .swim_stroke
+                    .map_or(false, |stroke| vel_map.is_active(stroke));
+                if !stroking {
+                    player_movement.swim_stroke =
+                        Some(vel_map.add_impulse(SWIM_STROKE_VEL, SWIM_STROKE_DUR, Decay::EaseOut));
+                    grav.velocity.y = 0.0;
+                }
+                continue;
             }
+
+            player_movement.jump_buffer.start();
+        }
+    }
+
+    for (mut player_movement, state_machine, mut grav, _, _) in player_query.iter_mut() {
+        let can_jump = state_machine.state().can_jump()
+            && player_movement.grounded.is_within(config.jump.coyote_time)
+            && player_movement
+                .jump_buffer
+                .is_within(config.jump.buffer_time);
+
+        if can_jump {
+            player_movement.velocity.y = config.jump.power;
+            player_movement.jumping = true;
+            player_movement.grounded.clear();
+            player_movement.jump_buffer.clear();
+            grav.velocity.y = 0.0;
         }
     }
 }
@@ -225,8 +355,8 @@ fn player_collision_system(
                 let player_y_speed = player.velocity.y.abs();
 
                 if let Some(grav_vel) = vel_map.get(grav.vel_id) {
-                    if !is_falling(grav_vel.y) && player_y_speed < GRAVITY && !player.can_jump {
-                        player.can_jump = true;
+                    if !is_falling(grav_vel.y) && player_y_speed < GRAVITY {
+                        player.grounded.start();
                     }
                 }
             }
@@ -270,16 +400,20 @@ fn is_falling(grav_y_vel: f32) -> bool {
 }
 
 /// Makes the player slow down while falling
-pub fn player_fall_system(mut player_query: Query<(&mut PlayerMovement, &mut Gravity)>) {
-    const PLAYER_FALL_MULTIPLIER: f32 = 1.2;
-
+pub fn player_fall_system(
+    mut player_query: Query<(&mut PlayerMovement, &mut Gravity)>,
+    section_gravity: Res<SectionGravity>,
+    config: Res<PlayerConfig>,
+) {
     for (mut player, mut gravity) in player_query.iter_mut() {
+        let scale = gravity.scale * section_gravity.0;
+
         if player.velocity.y > 0.0 {
             player.velocity.y += gravity.velocity.y;
             gravity.velocity = Vec2::ZERO;
-        } else if gravity.velocity.y < -(GRAVITY * 2.0) {
+        } else if gravity.velocity.y < -(GRAVITY * 2.0 * scale) {
             // TODO maybe this should be a gravity scale in the gravity component
-            gravity.velocity.y -= GRAVITY * PLAYER_FALL_MULTIPLIER;
+            gravity.velocity.y -= GRAVITY * config.jump.fall_multiplier * scale;
         }
     }
 }
@@ -296,49 +430,87 @@ fn add_player_velocity_system(mut query: Query<(&mut VelocityMap, &PlayerMovemen
 
 #[derive(Debug, Component)]
 pub struct PlayerSpawn {
-    vel_id: VelocityId,
-    spawn_time: Option<Instant>,
+    /// Pushes the player out of the connection they spawned at
+    spawn_push: Option<ImpulseId>,
+    /// Where the player entered the current room, they respawn there after dying
+    pub respawn_point: Vec3,
+    /// When the player died, they respawn after a delay
+    died: TimeWindow,
 }
 
 impl PlayerSpawn {
-    pub fn new(vel_map: &mut VelocityMap) -> Self {
+    pub fn new(respawn_point: Vec3) -> Self {
         Self {
-            vel_id: vel_map.register().0,
-            spawn_time: None,
+            spawn_push: None,
+            respawn_point,
+            died: TimeWindow::default(),
         }
     }
 
-    pub fn spawn_from_bottom(&mut self) {
-        self.spawn_time = Some(Instant::now());
+    pub fn spawn_from_bottom(&mut self, vel_map: &mut VelocityMap) {
+        const SPAWN_VEL_DUR: Duration = Duration::from_millis(400);
+        const SPAWN_VEL: Vec2 = Vec2::new(-4.0, 4.0);
+
+        self.spawn_push = Some(vel_map.add_timed(SPAWN_VEL, SPAWN_VEL_DUR));
     }
 }
 
+/// Keeps the player from moving and falling while being pushed out of a connection
 fn player_spawn_help_system(
     mut query: Query<(
-        &PlayerSpawn,
+        &mut PlayerSpawn,
         &mut Gravity,
         &mut PlayerMovement,
-        &mut VelocityMap,
+        &VelocityMap,
     )>,
 ) {
-    const SPAWN_VEL_DUR: Duration = Duration::from_millis(400);
-    const SPAWN_VEL: Vec2 = Vec2::new(-4.0, 4.0);
-
-    for (spawn, mut grav, mut mov, mut vel_map) in query.iter_mut() {
-        if let Some(spawn_time) = spawn.spawn_time {
-            if let Some(vel) = vel_map.get_mut(spawn.vel_id) {
-                if spawn_time.elapsed() < SPAWN_VEL_DUR {
-                    *vel = SPAWN_VEL;
-                    mov.velocity = Vec2::ZERO;
-                    grav.velocity = Vec2::ZERO;
-                } else {
-                    *vel = Vec2::ZERO;
-                }
+    for (mut spawn, mut grav, mut mov, vel_map) in query.iter_mut() {
+        if let Some(spawn_push) = spawn.spawn_push {
+            if vel_map.is_active(spawn_push) {
+                mov.velocity = Vec2::ZERO;
+                grav.velocity = Vec2::ZERO;
+            } else {
+                spawn.spawn_push = None;
             }
         }
     }
 }
 
+/// Moves dead players back to the point they entered the room at and heals them
+fn player_respawn_system(
+    mut death_reader: EventReader<PlayerDeathEvent>,
+    mut query: Query<(
+        &mut PlayerSpawn,
+        &mut PlayerHealth,
+        &mut PlayerStateMachine,
+        &mut Transform,
+        &mut PlayerMovement,
+        &mut Gravity,
+    )>,
+    config: Res<PlayerConfig>,
+) {
+    for PlayerDeathEvent(player) in death_reader.iter() {
+        if let Ok((mut spawn, ..)) = query.get_mut(*player) {
+            spawn.died.start();
+        }
+    }
+
+    for (mut spawn, mut health, mut state_machine, mut trans, mut mov, mut grav) in query.iter_mut()
+    {
+        match spawn.died.elapsed() {
+            Some(elapsed) if elapsed >= config.health.respawn_delay => (),
+            _ => continue,
+        }
+
+        spawn.died.clear();
+        trans.translation = spawn.respawn_point;
+        mov.velocity = Vec2::ZERO;
+        grav.velocity = Vec2::ZERO;
+        health.restore();
+        state_machine.respawn();
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct MouseCursor;
 
@@ -346,11 +518,24 @@ pub struct MouseCursor;
 pub fn move_cursor_system(
     mut cursor_query: Query<&mut Transform, With<MouseCursor>>,
     camera_query: Query<(&Camera, &Transform), Without<MouseCursor>>,
+    player_query: Query<&Transform, (With<PlayerMovement>, Without<MouseCursor>)>,
     windows: Res<Windows>,
+    aim: Res<Aim>,
 ) {
     const MOUSE_Z_POS: f32 = 7.0;
+    // How far the cursor is away from the player while aiming with the stick
+    const STICK_AIM_RADIUS: f32 = 48.0;
 
     for mut cursor_transform in cursor_query.iter_mut() {
+        if let Aim::Stick(direction) = *aim {
+            for player_transform in player_query.iter() {
+                cursor_transform.translation = (player_transform.translation.truncate()
+                    + direction * STICK_AIM_RADIUS)
+                    .extend(MOUSE_Z_POS);
+            }
+            continue;
+        }
+
         match camera_query.get_single() {
             Ok((camera, camera_transform)) => {
                 let win = if let Some(win) = windows.get_primary() {
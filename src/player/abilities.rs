@@ -9,53 +9,42 @@
 mod skills;
 
 pub mod collectibles;
+pub mod input;
+pub mod registry;
+pub mod upgrades;
 
 pub use skills::*;
 
-use bevy::{
-    ecs::system::EntityCommands,
-    prelude::*,
-    render::once_cell::sync::Lazy,
-    sprite::collide_aabb,
-    utils::{HashMap, Instant},
-};
+use input::InputMode;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
 use std::{
     any::TypeId,
-    fmt::{self, Debug, Formatter},
-    time::Duration,
+    borrow::Cow,
+    fmt::{self, Debug, Display, Formatter},
 };
 
-use super::{MouseCursor, PlayerMovement};
-use crate::{
-    collision::{BreakableCollider, Collider},
-    combat::Projectile,
-    enemies::EnemyHealth,
-    physics::VelocityMap,
-};
+use super::state::{PlayerState, PlayerStateMachine};
+
+/// The id of an ability item as it is used in the `map.toml`, each ability registers its own
+/// item in the [`AbilityRegistry`](registry::AbilityRegistry)
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq, Hash, Clone)]
+#[serde(transparent)]
+pub struct AbilityItem(Cow<'static, str>);
 
-// NOTE this would be nice if it was const (phf_map)
-pub static ABILITY_MAP: Lazy<HashMap<AbilityItem, AbilityDescriptor>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    map.insert(AbilityItem::Fire, PlayerDash::ability_descriptor());
-    map.insert(AbilityItem::Earth, PlayerShoot::ability_descriptor());
-    map.insert(AbilityItem::Water, PlayerWallJump::ability_descriptor());
-    map.insert(AbilityItem::Steam, PlayerDoubleJump::ability_descriptor());
-    map.insert(AbilityItem::Stone, PlayerCrouch::ability_descriptor());
-
-    map
-});
-
-#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq, Hash, Clone, Copy)]
-#[serde(rename_all = "snake_case")]
-pub enum AbilityItem {
-    Fire,
-    Earth,
-    Water,
-    Steam,
-    Stone,
+impl AbilityItem {
+    pub const fn new(id: &'static str) -> Self {
+        Self(Cow::Borrowed(id))
+    }
+}
+
+impl Display for AbilityItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct AbilityId(TypeId);
 
 pub trait Ability: Component + Default + Sized + 'static {
@@ -63,6 +52,17 @@ pub trait Ability: Component + Default + Sized + 'static {
         AbilityId(TypeId::of::<Self>())
     }
 
+    /// How the ability is triggered, by pressing its input by default
+    fn input_mode() -> InputMode {
+        InputMode::Press
+    }
+
+    /// Whether the state is only left by the systems of this ability,
+    /// it is left as well when the ability is swapped out
+    fn owns_state(_state: PlayerState) -> bool {
+        false
+    }
+
     fn unequip(player: &mut EntityCommands, inventory: &mut PlayerInventory) {
         player.remove::<Self>();
         inventory.unequip(Self::ability_id());
@@ -78,6 +78,21 @@ pub trait Ability: Component + Default + Sized + 'static {
             id: Self::ability_id(),
             unequip: Self::unequip,
             equip: Self::equip,
+            max_tier: 0,
+        }
+    }
+}
+
+/// Leaves the states of the ability `T` when it is not equipped anymore, its systems would never
+/// leave them otherwise
+pub fn release_state_system<T: Ability>(
+    mut query: Query<(&mut PlayerStateMachine, &PlayerInventory, Option<&T>)>,
+) {
+    for (mut state_machine, inventory, ability) in query.iter_mut() {
+        if T::owns_state(state_machine.state())
+            && (ability.is_none() || inventory.get_equipped_at::<T>().is_none())
+        {
+            state_machine.request(PlayerState::Airborne);
         }
     }
 }
@@ -87,6 +102,8 @@ pub struct AbilityDescriptor {
     id: AbilityId,
     unequip: fn(&mut EntityCommands, &mut PlayerInventory),
     equip: fn(&mut EntityCommands, &mut PlayerInventory, equip_slot: EquipSlot),
+    /// The highest upgrade tier, 0 for abilities without upgrades
+    max_tier: usize,
 }
 
 impl AbilityDescriptor {
@@ -94,6 +111,11 @@ impl AbilityDescriptor {
         &self.id
     }
 
+    /// The highest upgrade tier of the ability
+    pub fn max_tier(&self) -> usize {
+        self.max_tier
+    }
+
     #[allow(dead_code)] // NOTE will be used later
     pub fn unequip(&self, entity: &mut EntityCommands, inventory: &mut PlayerInventory) {
         (self.unequip)(entity, inventory);
@@ -164,6 +186,7 @@ impl PlayerInventory {
         }
     }
 
+    #[allow(dead_code)] // NOTE will be used later
     pub fn is_equipped_at<T: Ability>(&self, slot: EquipSlot) -> bool {
         match slot {
             EquipSlot::Left => T::ability_id() == self.0,
@@ -194,167 +217,8 @@ impl PlayerInventory {
     // }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EquipSlot {
     Left,
     Right,
 }
-
-impl EquipSlot {
-    fn from_mouse_btn(btn: MouseButton) -> Option<Self> {
-        match btn {
-            MouseButton::Left => Some(EquipSlot::Left),
-            MouseButton::Right => Some(EquipSlot::Right),
-            _ => None,
-        }
-    }
-
-    pub fn to_mouse_btn(self) -> MouseButton {
-        match self {
-            Self::Left => MouseButton::Left,
-            Self::Right => MouseButton::Right,
-        }
-    }
-
-    pub fn from_equipkey(equip_key: KeyCode) -> Option<Self> {
-        match equip_key {
-            KeyCode::Q => Some(Self::Left),
-            KeyCode::E => Some(Self::Right),
-            _ => None,
-        }
-    }
-}
-
-const PLAYER_SHOT_DAMAGE: u32 = 2;
-
-/// The shooting ability, currently the `Earth` ability
-#[derive(Debug, Component)]
-pub struct PlayerShoot {
-    last_shot: Instant,
-}
-
-impl Ability for PlayerShoot {}
-
-impl Default for PlayerShoot {
-    fn default() -> Self {
-        Self {
-            last_shot: Instant::now(),
-        }
-    }
-}
-
-pub fn player_shoot_system(
-    mut commands: Commands,
-    mouse_input: ResMut<Input<MouseButton>>,
-    mut player_query: Query<(&Transform, &PlayerInventory, &mut PlayerShoot)>,
-    cursor_query: Query<&Transform, With<MouseCursor>>,
-) {
-    const PLAYER_PROJECTILE_SPEED: f32 = 5.5;
-    const PLAYER_SHOOT_INTERVAL: Duration = Duration::from_millis(250);
-    const PLAYER_SHOT_SIZE: f32 = 4.0;
-
-    for click in mouse_input.get_pressed() {
-        for (player_transform, inv, mut player_shoot) in player_query.iter_mut() {
-            if !EquipSlot::from_mouse_btn(*click)
-                .map_or(false, |slot| inv.is_equipped_at::<PlayerShoot>(slot))
-            {
-                continue;
-            }
-
-            if player_shoot.last_shot.elapsed() < PLAYER_SHOOT_INTERVAL {
-                continue;
-            }
-
-            player_shoot.last_shot = Instant::now();
-
-            for cursor in cursor_query.iter() {
-                let direction = -(player_transform.translation - cursor.translation)
-                    .normalize()
-                    .truncate();
-                let projectile = Projectile {
-                    speed: PLAYER_PROJECTILE_SPEED,
-                    direction,
-                    vel_id: None,
-                };
-                let size = Vec2::splat(PLAYER_SHOT_SIZE);
-                commands
-                    .spawn_bundle(SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::rgb(0.0, 1.0, 1.0),
-                            custom_size: Some(size),
-                            ..Default::default()
-                        },
-                        transform: Transform::from_translation(player_transform.translation),
-                        ..Default::default()
-                    })
-                    .insert(VelocityMap::new())
-                    .insert(PlayerShotProjectile::new(size))
-                    .insert(projectile);
-            }
-        }
-    }
-}
-
-/// Component for projectiles that can break breakable walls
-#[derive(Debug, Component)]
-pub struct PlayerShotProjectile {
-    pub size: Vec2,
-    creation_time: Instant,
-}
-
-impl PlayerShotProjectile {
-    fn new(size: Vec2) -> Self {
-        Self {
-            size,
-            creation_time: Instant::now(),
-        }
-    }
-}
-
-/// System that destroys breakable colliders with player projectiles
-pub fn player_shot_collision_system(
-    mut commands: Commands,
-    shot_query: Query<(&Transform, &PlayerShotProjectile, Entity)>,
-    mut collision_query: Query<
-        (
-            &Transform,
-            &Collider,
-            Option<&mut EnemyHealth>,
-            Option<&BreakableCollider>,
-            Entity,
-        ),
-        Without<PlayerMovement>,
-    >,
-) {
-    'outer: for (shot_trans, shot, shot_entity) in shot_query.iter() {
-        for (coll_trans, collider, enemy_health, breakable, wall_entity) in
-            collision_query.iter_mut()
-        {
-            if !collider.filter.collides_with_bullets() {
-                continue;
-            }
-
-            let collision = collide_aabb::collide(
-                shot_trans.translation,
-                shot.size,
-                coll_trans.translation,
-                collider.size,
-            );
-            if collision.map_or(false, |coll| collider.filter.is_collision(&coll)) {
-                commands.entity(shot_entity).despawn();
-                if breakable.is_some() {
-                    commands.entity(wall_entity).despawn();
-                }
-
-                if let Some(mut health) = enemy_health {
-                    *health -= PLAYER_SHOT_DAMAGE;
-                }
-                continue 'outer;
-            }
-        }
-
-        if shot.creation_time.elapsed() > Duration::from_secs(30) {
-            commands.entity(shot_entity).despawn();
-        }
-    }
-}
@@ -1,109 +1,82 @@
-use bevy::{prelude::*, sprite::collide_aabb};
+use bevy::prelude::*;
 
-use crate::collision::Collider;
+use crate::{
+    collision::triggers::{TriggerEvent, TriggerEventKind},
+    controls::Action,
+};
 
-use super::{Ability, AbilityDescriptor, EquipSlot, PlayerInventory};
+use super::{registry::AbilityRegistry, AbilityItem, PlayerInventory};
 
+/// An ability which can be collected while the player is inside its trigger
 #[derive(Component, Debug)]
 pub struct CollectibleAbilityTrigger {
-    pub size: Vec2,
-    pub offset: Vec3,
-    ability: AbilityDescriptor,
+    item: AbilityItem,
 }
 
 impl CollectibleAbilityTrigger {
-    #[allow(dead_code)] // Useful for testing
-    pub fn new<T: Ability>(size: Vec2, offset: Vec3) -> Self {
-        Self::new_with_descriptor(size, offset, T::ability_descriptor())
-    }
-
-    pub fn new_with_descriptor(size: Vec2, offset: Vec3, ability: AbilityDescriptor) -> Self {
-        Self {
-            size,
-            offset,
-            ability,
-        }
+    pub fn new(item: AbilityItem) -> Self {
+        Self { item }
     }
 }
 
 pub fn collect_ability_system(
     mut commands: Commands,
-    trigger_query: Query<(&CollectibleAbilityTrigger, &Transform, Entity)>,
-    mut player_query: Query<(
-        &Transform,
-        &Collider,
-        &mut Sprite,
-        &mut PlayerInventory,
-        Entity,
-    )>,
-    key_events: ResMut<Input<KeyCode>>,
+    mut trigger_reader: EventReader<TriggerEvent>,
+    collectible_query: Query<&CollectibleAbilityTrigger>,
+    mut player_query: Query<(&mut Sprite, &mut PlayerInventory)>,
+    actions: Res<Input<Action>>,
+    registry: Res<AbilityRegistry>,
 ) {
-    let mut mark = false;
+    for evt in trigger_reader.iter() {
+        let (collectible, (mut player_sprite, mut inventory)) = match (
+            collectible_query.get(evt.trigger),
+            player_query.get_mut(evt.entity),
+        ) {
+            (Ok(collectible), Ok(player)) => (collectible, player),
+            _ => continue,
+        };
 
-    for (player_transform, player_collider, mut player_sprite, mut inventory, player_entity) in
-        player_query.iter_mut()
-    {
-        for (trigger, trigger_transform, trigger_entity) in trigger_query.iter() {
-            let collision = collide_aabb::collide(
-                player_transform.translation,
-                player_collider.size,
-                trigger_transform.translation + trigger.offset,
-                trigger.size,
-            );
-
-            if let Some(_col) = collision {
-                mark = true;
-                for key in key_events.get_just_pressed() {
-                    if let Some(equip_slot) = EquipSlot::from_equipkey(*key) {
-                        commands.entity(trigger_entity).despawn();
-                        mark = false;
-                        trigger.ability.equip(
-                            &mut commands.entity(player_entity),
-                            &mut inventory,
-                            equip_slot,
-                        );
-                    }
-                }
-            }
+        if evt.kind == TriggerEventKind::Exit {
+            player_sprite.color = Color::WHITE;
+            continue;
         }
 
-        if mark {
-            player_sprite.color = Color::GREEN;
-        } else {
+        player_sprite.color = Color::GREEN;
+        if let Some(equip_slot) = actions.get_just_pressed().find_map(|action| match action {
+            Action::Equip(slot) => Some(*slot),
+            _ => None,
+        }) {
+            commands.entity(evt.trigger).despawn();
+            // The trigger is gone, so there won't be an exit event
             player_sprite.color = Color::WHITE;
+            registry
+                .get(&collectible.item)
+                .expect("The abilities of the items are checked when loading the room")
+                .equip(&mut commands.entity(evt.entity), &mut inventory, equip_slot);
         }
     }
 }
 
+/// The altar to combine the equipped abilities at, active while the player is inside its trigger
 #[derive(Component, Debug)]
-pub struct CombineAltar {
-    pub size: Vec2,
-    pub offset: Vec3,
-}
+pub struct CombineAltar;
 
 pub fn combine_altar_system(
-    altar_query: Query<(&CombineAltar, &Transform)>,
-    mut player_query: Query<(&Collider, &Transform, &mut Sprite, &PlayerInventory)>,
+    mut trigger_reader: EventReader<TriggerEvent>,
+    altar_query: Query<(), With<CombineAltar>>,
+    mut player_query: Query<&mut Sprite, With<PlayerInventory>>,
 ) {
-    for (player_collider, player_transform, mut player_sprite, inv) in player_query.iter_mut() {
-        let mut mark = false;
-        for (altar, altar_transform) in altar_query.iter() {
-            let collision = collide_aabb::collide(
-                player_transform.translation,
-                player_collider.size,
-                altar_transform.translation + altar.offset,
-                altar.size,
-            );
+    for evt in trigger_reader.iter() {
+        if altar_query.get(evt.trigger).is_err() {
+            continue;
+        }
 
-            if collision.is_some() {
-                mark = true;
+        if let Ok(mut player_sprite) = player_query.get_mut(evt.entity) {
+            if evt.is_inside() {
+                player_sprite.color = Color::BLUE;
+            } else {
+                player_sprite.color = Color::WHITE;
             }
         }
-        
-        if mark {
-            player_sprite.color = Color::BLUE;
-        } else {
-            player_sprite.color = Color::WHITE;
-        }
     }
 }
@@ -0,0 +1,99 @@
+//! The input of the equipped abilities, either triggered by pressing or charged by holding
+
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::Instant};
+
+use super::{Ability, EquipSlot, PlayerInventory};
+use crate::{
+    controls::Action,
+    player::state::{PlayerState, PlayerStateMachine},
+};
+
+/// How an ability is triggered by its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    /// Triggered when the input is pressed
+    Press,
+    /// Charged while the input is held and triggered when it is released,
+    /// fully charged after `full_charge`
+    Charge { full_charge: Duration },
+}
+
+/// The input of both equip slots, interpreted by the [`InputMode`] of the equipped abilities
+#[derive(Component, Debug, Default)]
+pub struct AbilityInput {
+    left: SlotInput,
+    right: SlotInput,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SlotInput {
+    just_pressed: bool,
+    /// Since when the input is held, `None` when it was cancelled
+    held_since: Option<Instant>,
+    /// How long the input was held when it was released in this frame
+    released_after: Option<Duration>,
+}
+
+impl AbilityInput {
+    fn slot(&self, slot: EquipSlot) -> &SlotInput {
+        match slot {
+            EquipSlot::Left => &self.left,
+            EquipSlot::Right => &self.right,
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut SlotInput {
+        match slot {
+            EquipSlot::Left => &mut self.left,
+            EquipSlot::Right => &mut self.right,
+        }
+    }
+
+    /// Whether the ability `T` is triggered in this frame, with its charge from 0 to 1
+    pub fn trigger<T: Ability>(&self, inventory: &PlayerInventory) -> Option<f32> {
+        let slot = self.slot(inventory.get_equipped_at::<T>()?);
+
+        match T::input_mode() {
+            InputMode::Press => slot.just_pressed.then(|| 0.0),
+            InputMode::Charge { full_charge } => slot
+                .released_after
+                .map(|held| (held.as_secs_f32() / full_charge.as_secs_f32()).min(1.0)),
+        }
+    }
+}
+
+/// Tracks pressing, holding and releasing the use actions.
+///
+/// Charging is cancelled by the cancel action and by being stunned or dying.
+pub fn ability_input_system(
+    actions: Res<Input<Action>>,
+    mut query: Query<(&mut AbilityInput, &PlayerStateMachine)>,
+) {
+    for (mut input, state_machine) in query.iter_mut() {
+        let cancelled = actions.just_pressed(Action::Cancel)
+            || matches!(
+                state_machine.state(),
+                PlayerState::Stunned | PlayerState::Dead
+            );
+
+        for equip_slot in [EquipSlot::Left, EquipSlot::Right] {
+            let action = Action::Use(equip_slot);
+            let slot = input.slot_mut(equip_slot);
+
+            slot.just_pressed = actions.just_pressed(action);
+            slot.released_after = None;
+
+            if slot.just_pressed {
+                slot.held_since = Some(Instant::now());
+            }
+
+            if cancelled {
+                slot.held_since = None;
+            } else if actions.just_released(action) {
+                slot.released_after = slot.held_since.take().map(|since| since.elapsed());
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! The abilities known to the game, each ability registers itself with its item id and systems
+//! from its own plugin:
+//!
+//! ```ignore
+//! app.register_ability::<PlayerDash>(
+//!     "fire",
+//!     AbilitySystems {
+//!         late_update: SystemSet::new().with_system(player_dash_system),
+//!         ..Default::default()
+//!     },
+//! );
+//! ```
+//!
+//! Abilities with upgrades are registered with the type of their tiers instead, which are loaded
+//! from the `upgrades` of the [`PlayerConfig`] by the item id.
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::de::DeserializeOwned;
+
+use super::{
+    release_state_system, upgrades::UpgradeTiers, Ability, AbilityDescriptor, AbilityItem,
+};
+use crate::{player::config::PlayerConfig, LATE_UPDATE_STAGE, POST_COLLISION_STAGE};
+
+/// The abilities by the id of their item
+#[derive(Debug, Default)]
+pub struct AbilityRegistry(HashMap<AbilityItem, AbilityDescriptor>);
+
+impl AbilityRegistry {
+    pub fn get(&self, item: &AbilityItem) -> Option<&AbilityDescriptor> {
+        self.0.get(item)
+    }
+}
+
+/// The systems of an ability by the stage they run in
+#[derive(Default)]
+pub struct AbilitySystems {
+    pub update: SystemSet,
+    pub late_update: SystemSet,
+    pub post_collision: SystemSet,
+}
+
+pub trait RegisterAbility {
+    /// Register the ability `T` to be collected as the item `item_id` and add its systems
+    fn register_ability<T: Ability>(
+        &mut self,
+        item_id: &'static str,
+        systems: AbilitySystems,
+    ) -> &mut Self;
+
+    /// Register the ability `T` like [`RegisterAbility::register_ability`] and load its upgrade
+    /// tiers as the resource [`UpgradeTiers<Tier>`]
+    fn register_upgradable_ability<T: Ability, Tier: DeserializeOwned + Send + Sync + 'static>(
+        &mut self,
+        item_id: &'static str,
+        systems: AbilitySystems,
+    ) -> &mut Self;
+}
+
+impl RegisterAbility for App {
+    fn register_ability<T: Ability>(
+        &mut self,
+        item_id: &'static str,
+        systems: AbilitySystems,
+    ) -> &mut Self {
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(AbilityRegistry::default);
+        if registry
+            .0
+            .insert(AbilityItem::new(item_id), T::ability_descriptor())
+            .is_some()
+        {
+            panic!("The ability item {} is registered twice", item_id);
+        }
+
+        self.add_system(release_state_system::<T>)
+            .add_system_set(systems.update)
+            .add_system_set_to_stage(LATE_UPDATE_STAGE, systems.late_update)
+            .add_system_set_to_stage(POST_COLLISION_STAGE, systems.post_collision)
+    }
+
+    fn register_upgradable_ability<T: Ability, Tier: DeserializeOwned + Send + Sync + 'static>(
+        &mut self,
+        item_id: &'static str,
+        systems: AbilitySystems,
+    ) -> &mut Self {
+        let item = AbilityItem::new(item_id);
+        // The abilities may be registered before the player plugin is added
+        self.init_resource::<PlayerConfig>();
+        let tiers: Vec<Tier> = self
+            .world
+            .resource::<PlayerConfig>()
+            .upgrades
+            .tiers(&item)
+            .unwrap_or_else(|e| {
+                panic!("The upgrade tiers of the item {} are invalid: {}", item, e)
+            });
+        if tiers.is_empty() {
+            panic!("The ability item {} has no upgrade tiers", item);
+        }
+
+        self.register_ability::<T>(item_id, systems);
+        self.world
+            .resource_mut::<AbilityRegistry>()
+            .0
+            .get_mut(&item)
+            .expect("The ability is registered")
+            .max_tier = tiers.len() - 1;
+
+        self.insert_resource(UpgradeTiers(tiers))
+    }
+}
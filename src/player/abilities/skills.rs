@@ -1,9 +1,30 @@
+//! The skills of the abilities, each in its own plugin
+
+use bevy::{app::PluginGroupBuilder, prelude::*};
+
 mod crouch;
 mod dash;
 mod double_jump;
+mod shoot;
 mod wall_jump;
 
 pub use crouch::*;
 pub use dash::*;
 pub use double_jump::*;
+pub use shoot::*;
 pub use wall_jump::*;
+
+/// The plugins of all abilities
+#[derive(Debug)]
+pub struct AbilityPlugins;
+
+impl PluginGroup for AbilityPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(ShootPlugin)
+            .add(DashPlugin)
+            .add(WallJumpPlugin)
+            .add(DoubleJumpPlugin)
+            .add(CrouchPlugin);
+    }
+}
@@ -1,90 +1,203 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
+use serde::Deserialize;
 
 use crate::{
-    collision::{BreakableCollider, Collider},
-    physics::{Gravity, GRAVITY, GRAVITY_MAX},
+    collision::{
+        bodies::BodyCollisionLabel,
+        raycast::{CastFilter, ColliderCast},
+        BreakableCollider, Collider, CollisionLayers,
+    },
+    enemies::{EnemyHealth, EnemyStun},
+    map::TILE_SIZE,
+    physics::{Decay, Gravity, VelocityMap, GRAVITY, GRAVITY_MAX},
     player::{
-        abilities::{Ability, PlayerInventory},
+        abilities::{
+            input::AbilityInput,
+            registry::{AbilitySystems, RegisterAbility},
+            upgrades::{AbilityUpgrades, UpgradeTiers},
+            Ability, PlayerInventory,
+        },
+        state::{PlayerState, PlayerStateMachine},
         PlayerLandEvent, PlayerMovement,
     },
-    PLAYER_SIZE,
 };
 
+const CROUCH_SIZE: f32 = 4.0;
+/// The fall height of a shockwave with full strength
+const SHOCKWAVE_FULL_HEIGHT: f32 = TILE_SIZE * 8.0;
+/// Shorter crouch falls land without a shockwave
+const SHOCKWAVE_MIN_HEIGHT: f32 = TILE_SIZE * 2.0;
+/// How far above and below the landing the shockwave reaches, it runs along the ground
+const SHOCKWAVE_HEIGHT: f32 = TILE_SIZE;
+const SHOCKWAVE_DAMAGE: u32 = 4;
+const SHOCKWAVE_KNOCKBACK: f32 = 6.0;
+const SHOCKWAVE_KNOCKBACK_DURATION: Duration = Duration::from_millis(300);
+const SHOCKWAVE_STUN_DURATION: Duration = Duration::from_millis(2000);
+
+/// The `stone` ability
+#[derive(Debug)]
+pub struct CrouchPlugin;
+
+impl Plugin for CrouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_upgradable_ability::<PlayerCrouch, StoneTier>(
+            "stone",
+            AbilitySystems {
+                late_update: SystemSet::new().with_system(player_crouch_system),
+                post_collision: SystemSet::new()
+                    .with_system(crouch_ceiling_system.after(BodyCollisionLabel))
+                    .with_system(crouch_collision_system)
+                    .with_system(shockwave_system.after(crouch_collision_system)),
+                ..Default::default()
+            },
+        )
+        .add_event::<ShockwaveEvent>();
+    }
+}
+
+/// The stats of the crouch ability per upgrade tier
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct StoneTier {
+    /// The radius of a shockwave with full strength in pixels
+    pub shockwave_radius: f32,
+}
+
+/// Crouching in the air falls fast, breaking breakable colliders when landing on them and
+/// sending out a shockwave
 #[derive(Component, Debug, Default)]
 pub struct PlayerCrouch {
-    state: CrouchState,
+    /// The height the crouch fall started at
+    fall_start: Option<f32>,
+    /// The size and collision offset of the collider while standing, kept while crouching
+    standing: Option<(Vec2, Vec2)>,
+    /// Whether there is room to stand up
+    can_stand: bool,
+    /// Whether the player stands up as soon as there is room
+    wants_to_stand: bool,
 }
 
-impl Ability for PlayerCrouch {}
+impl PlayerCrouch {
+    /// Shrink the collider to the crouch size, keeping the feet of the player in place
+    fn crouch_down(&mut self, collider: &mut Collider, transform: &mut Transform) {
+        if self.standing.is_none() {
+            self.standing = Some((collider.size, collider.collision_offset));
+            transform.translation.y -= (collider.size.y - CROUCH_SIZE) / 2.0;
+            // The collision offset is relative to the size, so it stays aligned to the feet
+            let crouch_size = Vec2::splat(CROUCH_SIZE);
+            collider.collision_offset *= crouch_size / collider.size;
+            collider.size = crouch_size;
+            self.wants_to_stand = false;
+        }
+    }
+
+    /// Restore the standing collider, keeping the feet of the player in place
+    fn stand_up(&mut self, collider: &mut Collider, transform: &mut Transform) {
+        if let Some((size, collision_offset)) = self.standing.take() {
+            transform.translation.y += (size.y - collider.size.y) / 2.0;
+            collider.size = size;
+            collider.collision_offset = collision_offset;
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum CrouchState {
-    NotCrouching,
-    Airborne,
-    Grounded,
+/// Sent when the player lands from a crouch fall
+#[derive(Debug)]
+pub struct ShockwaveEvent {
+    pub origin: Vec2,
+    /// The ground the player landed on, which is broken already
+    pub ground: Entity,
+    /// The radius at full strength, grows with the upgrades of the ability
+    pub radius: f32,
+    /// Grows with the fall height up to 1
+    pub strength: f32,
 }
 
-impl Default for CrouchState {
-    fn default() -> Self {
-        Self::NotCrouching
+impl Ability for PlayerCrouch {
+    fn owns_state(state: PlayerState) -> bool {
+        matches!(state, PlayerState::Crouched { .. })
     }
 }
 
 pub fn player_crouch_system(
     mut query: Query<(
         &mut Gravity,
-        &mut PlayerCrouch,
+        &mut PlayerStateMachine,
         &mut PlayerMovement,
         &mut Collider,
+        &mut PlayerCrouch,
+        &mut Transform,
         &PlayerInventory,
+        &AbilityInput,
     )>,
-    mouse_input: ResMut<Input<MouseButton>>,
 ) {
     const CROUCH_FALL_SPEED: f32 = GRAVITY * 3.0;
     const MAX_CROUCH_FALL_SPEED: f32 = GRAVITY_MAX * 3.0;
-    const CROUCH_SIZE: f32 = 4.0;
-    // const CROUCH_SIZE: f32 = 16.0;
-
-    for (mut grav, mut crouch, mut player_move, mut moving_collider, inv) in query.iter_mut() {
-        if let Some(equip_slot) = inv.get_equipped_at::<PlayerCrouch>() {
-            loop {
-                match crouch.state {
-                    CrouchState::NotCrouching => {
-                        if mouse_input.just_pressed(equip_slot.to_mouse_btn()) {
-                            crouch.state = CrouchState::Airborne;
-                            moving_collider.size = Vec2::splat(CROUCH_SIZE);
-                            player_move.velocity.y = 0.0;
-                        } else {
-                            moving_collider.size = Vec2::splat(PLAYER_SIZE);
-                            break;
-                        }
+
+    for (
+        mut grav,
+        mut state_machine,
+        mut player_move,
+        mut moving_collider,
+        mut crouch,
+        mut transform,
+        inv,
+        input,
+    ) in query.iter_mut()
+    {
+        let triggered = input.trigger::<PlayerCrouch>(inv).is_some();
+        loop {
+            match state_machine.state() {
+                PlayerState::Crouched { grounded: false } => {
+                    crouch.fall_start.get_or_insert(transform.translation.y);
+                    grav.velocity = Vec2::ZERO;
+                    player_move.velocity.y =
+                        (player_move.velocity.y - CROUCH_FALL_SPEED).max(MAX_CROUCH_FALL_SPEED);
+
+                    player_move.velocity.x = 0.0;
+                    break;
+                }
+                PlayerState::Crouched { grounded: true } => {
+                    player_move.velocity.y = 0.0;
+                    if grav.velocity.y < -GRAVITY * 2.0 {
+                        state_machine.request(PlayerState::Crouched { grounded: false });
+                        continue;
                     }
-                    CrouchState::Airborne => {
-                        moving_collider.size = Vec2::splat(CROUCH_SIZE);
-                        grav.velocity = Vec2::ZERO;
-                        player_move.velocity.y =
-                            (player_move.velocity.y - CROUCH_FALL_SPEED).max(MAX_CROUCH_FALL_SPEED);
 
-                        player_move.velocity.x = 0.0;
+                    if triggered {
+                        crouch.wants_to_stand = true;
+                    }
+
+                    // Below a low ceiling the player keeps crouching until there is room
+                    if crouch.wants_to_stand
+                        && crouch.can_stand
+                        && state_machine.request(PlayerState::Grounded)
+                    {
+                        crouch.stand_up(&mut moving_collider, &mut transform);
                         break;
                     }
-                    CrouchState::Grounded => {
-                        moving_collider.size = Vec2::splat(CROUCH_SIZE);
-                        player_move.can_jump = false;
-                        player_move.velocity.y = 0.0;
-                        if grav.velocity.y < -GRAVITY * 2.0 {
-                            crouch.state = CrouchState::Airborne;
-                            continue;
-                        }
 
-                        if mouse_input.just_pressed(equip_slot.to_mouse_btn()) {
-                            crouch.state = CrouchState::NotCrouching;
-                            moving_collider.size = Vec2::splat(PLAYER_SIZE);
-                            player_move.can_jump = true;
-                            break;
+                    player_move.velocity.x /= 2.0;
+                    break;
+                }
+                // Hurt while crouching, the player stays small while knocked back
+                PlayerState::Stunned | PlayerState::Dead => {
+                    crouch.fall_start = None;
+                    break;
+                }
+                _ => {
+                    crouch.fall_start = None;
+                    if triggered && state_machine.request(PlayerState::Crouched { grounded: false })
+                    {
+                        crouch.crouch_down(&mut moving_collider, &mut transform);
+                        player_move.velocity.y = 0.0;
+                    } else {
+                        // Left crouching without standing up, e.g. by the ability being
+                        // swapped out, the player stays small until there is room
+                        if crouch.can_stand {
+                            crouch.stand_up(&mut moving_collider, &mut transform);
                         }
-
-                        player_move.velocity.x /= 2.0;
                         break;
                     }
                 }
@@ -93,21 +206,109 @@ pub fn player_crouch_system(
     }
 }
 
+/// Checks whether there is room for a crouching player to stand up, after the player was crouched
+/// and moved this frame
+pub fn crouch_ceiling_system(
+    mut query: Query<(&mut PlayerCrouch, &Transform, &Collider, Entity)>,
+    colliders: ColliderCast,
+) {
+    for (mut crouch, transform, collider, entity) in query.iter_mut() {
+        if let Some((standing_size, _)) = crouch.standing {
+            let center = transform.translation.truncate()
+                + Vec2::new(0.0, (standing_size.y - collider.size.y) / 2.0);
+            let filter = CastFilter::new(collider.layers, CollisionLayers::WORLD).ignoring(entity);
+
+            crouch.can_stand = colliders.overlap(center, standing_size, &filter).is_none();
+        }
+    }
+}
+
 pub fn crouch_collision_system(
     mut commands: Commands,
-    mut query: Query<&mut PlayerCrouch>,
+    mut query: Query<(
+        &mut PlayerStateMachine,
+        &mut PlayerCrouch,
+        &Transform,
+        &AbilityUpgrades,
+    )>,
     breakables_query: Query<Entity, With<BreakableCollider>>,
     mut event_reader: EventReader<PlayerLandEvent>,
+    mut shockwave_writer: EventWriter<ShockwaveEvent>,
+    tiers: Res<UpgradeTiers<StoneTier>>,
 ) {
     let mut landed_this_frame = false;
     for evt in event_reader.iter() {
-        if let Ok(mut crouch) = query.get_mut(evt.player_entity) {
-            if CrouchState::Airborne == crouch.state || landed_this_frame {
+        if let Ok((mut state_machine, mut crouch, transform, upgrades)) =
+            query.get_mut(evt.player_entity)
+        {
+            if state_machine.state() == (PlayerState::Crouched { grounded: false })
+                || landed_this_frame
+            {
                 if let Ok(ent) = breakables_query.get(evt.ground_entity) {
                     commands.entity(ent).despawn();
                 }
-                crouch.state = CrouchState::Grounded;
+                state_machine.request(PlayerState::Crouched { grounded: true });
                 landed_this_frame = true;
+
+                let fall_height = crouch
+                    .fall_start
+                    .take()
+                    .map(|fall_start| fall_start - transform.translation.y);
+                if let Some(fall_height) = fall_height.filter(|h| *h >= SHOCKWAVE_MIN_HEIGHT) {
+                    shockwave_writer.send(ShockwaveEvent {
+                        origin: transform.translation.truncate(),
+                        ground: evt.ground_entity,
+                        radius: upgrades.stats::<PlayerCrouch, _>(&tiers).shockwave_radius,
+                        strength: (fall_height / SHOCKWAVE_FULL_HEIGHT).min(1.0),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Breaks the breakable colliders left and right of the landing and hurts, knocks back and stuns
+/// the enemies on the same ground
+pub fn shockwave_system(
+    mut commands: Commands,
+    mut shockwave_reader: EventReader<ShockwaveEvent>,
+    breakables_query: Query<(&Transform, Entity), With<BreakableCollider>>,
+    mut enemy_query: Query<(
+        &Transform,
+        &mut EnemyHealth,
+        &mut VelocityMap,
+        Option<&mut EnemyStun>,
+    )>,
+) {
+    for evt in shockwave_reader.iter() {
+        let radius = evt.radius * evt.strength;
+
+        let reaches = |offset: Vec2| offset.x.abs() <= radius && offset.y.abs() <= SHOCKWAVE_HEIGHT;
+
+        for (breakable_trans, breakable) in breakables_query.iter() {
+            if breakable != evt.ground
+                && reaches(breakable_trans.translation.truncate() - evt.origin)
+            {
+                commands.entity(breakable).despawn();
+            }
+        }
+
+        for (enemy_trans, mut health, mut vel_map, stun) in enemy_query.iter_mut() {
+            let offset = enemy_trans.translation.truncate() - evt.origin;
+            if !reaches(offset) {
+                continue;
+            }
+
+            *health -= (SHOCKWAVE_DAMAGE as f32 * evt.strength).ceil() as u32;
+            // Always pushed upwards, so the enemy is thrown off the ground
+            let away = Vec2::new(offset.x.signum(), 1.0).normalize();
+            vel_map.add_impulse(
+                away * SHOCKWAVE_KNOCKBACK * evt.strength,
+                SHOCKWAVE_KNOCKBACK_DURATION,
+                Decay::EaseOut,
+            );
+            if let Some(mut stun) = stun {
+                stun.stun(SHOCKWAVE_STUN_DURATION.mul_f32(evt.strength));
             }
         }
     }
@@ -1,80 +1,302 @@
-use bevy::{prelude::*, utils::Instant};
-use std::{any::TypeId, time::Duration};
+use bevy::{prelude::*, sprite::collide_aabb, utils::Instant};
+use serde::Deserialize;
+use std::{f32::consts::FRAC_PI_4, time::Duration};
 
 use crate::{
+    collision::{BreakableCollider, Collider},
+    enemies::EnemyHealth,
     physics::Gravity,
     player::{
-        abilities::{Ability, EquipSlot, PlayerInventory},
-        PlayerMovement,
+        abilities::{
+            input::{AbilityInput, InputMode},
+            registry::{AbilitySystems, RegisterAbility},
+            upgrades::{AbilityUpgrades, UpgradeTiers},
+            Ability, PlayerInventory,
+        },
+        config::PlayerConfig,
+        state::{PlayerState, PlayerStateMachine},
+        MouseCursor, PlayerMovement,
     },
+    util::deserialize_millis,
 };
 
-const PLAYER_DASH_SPEED: f32 = 8.0;
-const PLAYER_DASH_INTERVAL: Duration = Duration::from_millis(1500);
 const PLAYER_RUN_EPSILON: f32 = 0.2;
 const PLAYER_DASH_DURATION: Duration = Duration::from_millis(150);
+/// How much longer a fully charged dash lasts
+const PLAYER_DASH_CHARGE_DURATION: Duration = Duration::from_millis(150);
+const PLAYER_DASH_DAMAGE: u32 = 3;
 
+/// The `fire` ability
+#[derive(Debug)]
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_upgradable_ability::<PlayerDash, FireTier>(
+            "fire",
+            AbilitySystems {
+                late_update: SystemSet::new()
+                    .with_system(player_dash_system)
+                    .with_system(player_dash_impact_system.after(player_dash_system)),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// The stats of the dash ability per upgrade tier
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FireTier {
+    pub speed: f32,
+    /// How long until the player can dash again
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub interval: Duration,
+}
+
+/// How the dash is aimed
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DashMode {
+    /// Dashing horizontally in the direction the player is running
+    Horizontal,
+    /// Dashing in one of eight directions towards the cursor, even from standstill.
+    /// Breaks the breakable walls and hurts the enemies in its way.
+    Directional,
+}
+
+/// Charging the dash makes it last longer
 #[derive(Component, Debug, Default)]
 pub struct PlayerDash {
     pub(crate) last_dash: Option<Instant>,
+    /// Whether the dash is still moving the player
+    dashing: bool,
+    /// The direction of the current dash
+    direction: Vec2,
+    /// How long the current dash lasts, longer when it was charged
+    duration: Duration,
+    /// The enemies already hurt by the current dash
+    hit: Vec<Entity>,
 }
 
-impl Ability for PlayerDash {}
+impl PlayerDash {
+    fn start(&mut self, direction: Vec2, duration: Duration) {
+        self.last_dash = Some(Instant::now());
+        self.dashing = true;
+        self.direction = direction;
+        self.duration = duration;
+        self.hit.clear();
+    }
+
+    /// Stop moving the player when the dash ended or was interrupted. The vertical speed of the
+    /// dash is dropped, it would push the player into the ground for good otherwise.
+    fn stop(&mut self, velocity: &mut Vec2) {
+        if self.dashing {
+            self.dashing = false;
+            velocity.y = 0.0;
+        }
+    }
+}
+
+impl Ability for PlayerDash {
+    fn owns_state(state: PlayerState) -> bool {
+        state == PlayerState::Dashing
+    }
+
+    fn input_mode() -> InputMode {
+        InputMode::Charge {
+            full_charge: Duration::from_millis(600),
+        }
+    }
+}
 
 pub fn player_dash_system(
-    mouse_input: ResMut<Input<MouseButton>>,
     mut player_query: Query<(
         &mut PlayerDash,
         &mut PlayerMovement,
+        &mut PlayerStateMachine,
         &mut Gravity,
         &PlayerInventory,
+        &AbilityInput,
+        &AbilityUpgrades,
+        &Transform,
     )>,
+    cursor_query: Query<&Transform, (With<MouseCursor>, Without<PlayerDash>)>,
+    config: Res<PlayerConfig>,
+    tiers: Res<UpgradeTiers<FireTier>>,
 ) {
-    for click in mouse_input.get_pressed() {
-        for (mut player_dash, _, _, inv) in player_query.iter_mut() {
-            if !EquipSlot::from_mouse_btn(*click)
-                .map_or(false, |slot| inv.is_equipped_at::<PlayerDash>(slot))
+    for (mut player_dash, player, mut state_machine, _, inv, input, _, player_trans) in
+        player_query.iter_mut()
+    {
+        let charge = match input.trigger::<PlayerDash>(inv) {
+            Some(charge) => charge,
+            None => continue,
+        };
+
+        if player_dash.last_dash.is_some() {
+            continue;
+        }
+
+        // Dashing needs a direction to dash in
+        let direction = match config.dash.mode {
+            DashMode::Horizontal if player.velocity.x.abs() < PLAYER_RUN_EPSILON => continue,
+            DashMode::Horizontal => Vec2::new(player.velocity.x.signum(), 0.0),
+            DashMode::Directional => match cursor_query
+                .iter()
+                .next()
+                .and_then(|cursor| eight_way(cursor.translation - player_trans.translation))
             {
-                continue;
-            }
+                Some(direction) => direction,
+                None => continue,
+            },
+        };
 
-            if player_dash.last_dash.is_some() {
-                continue;
+        if state_machine.request(PlayerState::Dashing) {
+            player_dash.start(
+                direction,
+                PLAYER_DASH_DURATION + PLAYER_DASH_CHARGE_DURATION.mul_f32(charge),
+            );
+        }
+    }
+
+    for (mut player_dash, mut player, mut state_machine, mut gravity, _, _, upgrades, _) in
+        player_query.iter_mut()
+    {
+        let stats = upgrades.stats::<PlayerDash, _>(&tiers);
+
+        let dash_over = match player_dash.last_dash {
+            None => true,
+            Some(last_dash) if last_dash.elapsed() > stats.interval => {
+                player_dash.last_dash = None;
+                true
             }
+            Some(last_dash) => last_dash.elapsed() > player_dash.duration,
+        };
 
-            player_dash.last_dash = Some(Instant::now());
+        // The dash is over or was interrupted
+        if dash_over || state_machine.state() != PlayerState::Dashing {
+            if dash_over && state_machine.state() == PlayerState::Dashing {
+                state_machine.request(PlayerState::Airborne);
+            }
+            player_dash.stop(&mut player.velocity);
+            continue;
         }
+
+        player.velocity = player_dash.direction * stats.speed;
+        gravity.velocity.y = 0.0;
+    }
+}
+
+/// Lets a directional dash break the walls and hurt the enemies in its way
+pub fn player_dash_impact_system(
+    mut commands: Commands,
+    mut player_query: Query<(
+        &mut PlayerDash,
+        &PlayerStateMachine,
+        &Transform,
+        &Collider,
+        &AbilityUpgrades,
+    )>,
+    mut target_query: Query<
+        (
+            &Transform,
+            &Collider,
+            Option<&mut EnemyHealth>,
+            Option<&BreakableCollider>,
+            Entity,
+        ),
+        Without<PlayerDash>,
+    >,
+    config: Res<PlayerConfig>,
+    tiers: Res<UpgradeTiers<FireTier>>,
+) {
+    if config.dash.mode != DashMode::Directional {
+        return;
     }
 
-    for (mut player_dash, mut player, mut gravity, _) in player_query.iter_mut() {
-        match player_dash.last_dash {
-            None => {
+    for (mut player_dash, state_machine, player_trans, player_collider, upgrades) in
+        player_query.iter_mut()
+    {
+        if state_machine.state() != PlayerState::Dashing {
+            continue;
+        }
+
+        // Where the player is moved to in this frame, walls are broken before colliding with them
+        let speed = upgrades.stats::<PlayerDash, _>(&tiers).speed;
+        let next_translation =
+            player_trans.translation + (player_dash.direction * speed).extend(0.0);
+
+        for (target_trans, target_collider, enemy_health, breakable, target) in
+            target_query.iter_mut()
+        {
+            if collide_aabb::collide(
+                next_translation,
+                player_collider.size,
+                target_trans.translation,
+                target_collider.size,
+            )
+            .is_none()
+            {
                 continue;
             }
-            Some(last_dash) if last_dash.elapsed() > PLAYER_DASH_INTERVAL => {
-                player.move_forbid_set.remove(&TypeId::of::<PlayerDash>());
-                player_dash.last_dash = None;
-                continue;
+
+            if breakable.is_some() {
+                commands.entity(target).despawn();
             }
-            Some(last_dash) if last_dash.elapsed() > PLAYER_DASH_DURATION => {
-                player.move_forbid_set.remove(&TypeId::of::<PlayerDash>());
-                continue;
+
+            if let Some(mut health) = enemy_health {
+                if !player_dash.hit.contains(&target) {
+                    *health -= PLAYER_DASH_DAMAGE;
+                    player_dash.hit.push(target);
+                }
             }
-            Some(_) => (),
         }
+    }
+}
 
-        if player.velocity.x < PLAYER_RUN_EPSILON && player.velocity.x > -PLAYER_RUN_EPSILON {
-            continue;
-        }
+/// Snaps the direction to the closest of the eight directions, `None` without a direction
+fn eight_way(direction: Vec3) -> Option<Vec2> {
+    let direction = direction.truncate();
+    if direction == Vec2::ZERO {
+        return None;
+    }
+
+    let angle = (direction.y.atan2(direction.x) / FRAC_PI_4).round() * FRAC_PI_4;
+    Some(Vec2::new(angle.cos(), angle.sin()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::GRAVITY;
+
+    const SPEED: f32 = 8.0;
+
+    #[test]
+    fn landing_after_a_downward_dash() {
+        for direction in [Vec2::new(0.0, -1.0), Vec2::new(1.0, -1.0).normalize()] {
+            let mut dash = PlayerDash::default();
+            dash.start(direction, PLAYER_DASH_DURATION);
+            let mut velocity = dash.direction * SPEED;
 
-        if player.velocity.x > PLAYER_RUN_EPSILON {
-            player.velocity.x = PLAYER_DASH_SPEED;
-        } else if player.velocity.x < -PLAYER_RUN_EPSILON {
-            player.velocity.x = -PLAYER_DASH_SPEED;
+            dash.stop(&mut velocity);
+            // Fast enough downwards the landing wouldn't count as standing on the ground
+            assert!(velocity.y.abs() < GRAVITY, "{:?}", direction);
+            assert_eq!(velocity.x, direction.x * SPEED);
         }
+    }
 
-        player.velocity.y = 0.0;
-        gravity.velocity.y = 0.0;
-        player.move_forbid_set.insert(TypeId::of::<PlayerDash>());
+    #[test]
+    fn stopping_without_a_dash_keeps_the_velocity() {
+        let mut dash = PlayerDash::default();
+        let mut velocity = Vec2::new(1.0, 9.0);
+
+        dash.stop(&mut velocity);
+        assert_eq!(velocity, Vec2::new(1.0, 9.0));
+
+        dash.start(Vec2::X, PLAYER_DASH_DURATION);
+        dash.stop(&mut velocity);
+        velocity.y = 9.0;
+        dash.stop(&mut velocity);
+        assert_eq!(velocity.y, 9.0);
     }
 }
@@ -1,53 +1,228 @@
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::Instant};
+use serde::Deserialize;
 
 use crate::{
+    asset_loaders::{cache::AssetCache, EmbeddedAssets},
+    collision::{Collider, CollisionFilter, CollisionLayers},
+    map::{MapManager, TILE_SIZE},
     physics::Gravity,
     player::{
-        abilities::{Ability, EquipSlot, PlayerInventory},
+        abilities::{
+            input::AbilityInput,
+            registry::{AbilitySystems, RegisterAbility},
+            upgrades::{AbilityUpgrades, UpgradeTiers},
+            Ability, PlayerInventory,
+        },
+        config::PlayerConfig,
+        state::PlayerStateMachine,
         PlayerLandEvent, PlayerMovement,
     },
+    util::TimeWindow,
 };
 
+const CLOUD_SIZE: Vec2 = Vec2::new(TILE_SIZE * 3.0, TILE_SIZE);
+const CLOUD_LIFETIME: Duration = Duration::from_millis(1500);
+/// How long the cloud fades out at the end of its lifetime
+const CLOUD_FADE_DURATION: Duration = Duration::from_millis(500);
+const PUFF_LIFETIME: Duration = Duration::from_millis(300);
+/// The scale of the puff when it vanishes
+const PUFF_MAX_SCALE: f32 = 2.0;
+
+/// The `steam` ability
+#[derive(Debug)]
+pub struct DoubleJumpPlugin;
+
+impl Plugin for DoubleJumpPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_upgradable_ability::<PlayerDoubleJump, SteamTier>(
+            "steam",
+            AbilitySystems {
+                update: SystemSet::new().with_system(steam_cloud_system),
+                late_update: SystemSet::new().with_system(player_double_jump_system),
+                post_collision: SystemSet::new().with_system(double_jump_land_system),
+            },
+        );
+    }
+}
+
+/// The stats of the double jump ability per upgrade tier
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct SteamTier {
+    pub power: f32,
+}
+
+/// Double jumping leaves a steam cloud below the player, which can be stood on for a short time
 #[derive(Component, Debug)]
 pub struct PlayerDoubleJump {
     can_dbl_jump: bool,
+    /// When the double jump button was pressed
+    jump_buffer: TimeWindow,
 }
 
 impl Default for PlayerDoubleJump {
     fn default() -> Self {
-        PlayerDoubleJump { can_dbl_jump: true }
+        PlayerDoubleJump {
+            can_dbl_jump: true,
+            jump_buffer: TimeWindow::default(),
+        }
     }
 }
 
 impl Ability for PlayerDoubleJump {}
 
+/// A one-way platform left by the double jump, vanishing after a short time
+#[derive(Component, Debug)]
+pub struct SteamCloud {
+    spawned: Instant,
+}
+
+/// The puff of steam of a double jump, growing and fading out
+#[derive(Component, Debug)]
+pub struct SteamPuff {
+    spawned: Instant,
+}
+
 pub fn player_double_jump_system(
-    mouse_input: ResMut<Input<MouseButton>>,
+    mut commands: Commands,
     mut query: Query<(
         &mut Gravity,
         &mut PlayerDoubleJump,
         &mut PlayerMovement,
+        &PlayerStateMachine,
         &PlayerInventory,
+        &AbilityInput,
+        &AbilityUpgrades,
+        &Transform,
+        &Collider,
     )>,
+    mut asset_cache: ResMut<AssetCache<EmbeddedAssets>>,
+    mut assets: ResMut<Assets<Image>>,
+    config: Res<PlayerConfig>,
+    tiers: Res<UpgradeTiers<SteamTier>>,
+    map: Res<MapManager>,
 ) {
-    const DOUBLE_JUMP_POWER: f32 = 10.5;
-    // const DOUBLE_JUMP_POWER: f32 = 18.5;
-
-    for (mut grav, mut jump, mut player_mov, inv) in query.iter_mut() {
-        if let Some(mouse_button) = inv
-            .get_equipped_at::<PlayerDoubleJump>()
-            .map(EquipSlot::to_mouse_btn)
-        {
-            if mouse_input.just_pressed(mouse_button) && jump.can_dbl_jump {
+    for (
+        mut grav,
+        mut jump,
+        mut player_mov,
+        state_machine,
+        inv,
+        input,
+        upgrades,
+        transform,
+        collider,
+    ) in query.iter_mut()
+    {
+        if inv.get_equipped_at::<PlayerDoubleJump>().is_some() {
+            if input.trigger::<PlayerDoubleJump>(inv).is_some() {
+                jump.jump_buffer.start();
+            }
+
+            if jump.jump_buffer.is_within(config.jump.buffer_time)
+                && jump.can_dbl_jump
+                && state_machine.state().can_jump()
+            {
                 grav.velocity = Vec2::ZERO;
-                player_mov.velocity.y = DOUBLE_JUMP_POWER;
-                player_mov.can_jump = false;
+                player_mov.velocity.y = upgrades.stats::<PlayerDoubleJump, _>(&tiers).power;
+                player_mov.grounded.clear();
                 jump.can_dbl_jump = false;
+                jump.jump_buffer.clear();
+
+                let texture = asset_cache
+                    .load_image(&mut assets, "sprites/character/skills/doublejump_cloud.png")
+                    .unwrap_or_else(|e| panic!("The steam cloud could not be loaded: {}", e));
+                let below_player = transform.translation
+                    - Vec3::new(0.0, (collider.size.y + CLOUD_SIZE.y) / 2.0, 0.0);
+                spawn_steam_cloud(&mut commands, texture, below_player, map.current_room());
             }
         }
     }
 }
 
+/// Spawns the cloud and its puff inside the room, so they are gone when the room changes
+fn spawn_steam_cloud(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    at: Vec3,
+    room: Option<Entity>,
+) {
+    let cloud = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(CLOUD_SIZE),
+                ..Default::default()
+            },
+            texture: texture.clone(),
+            transform: Transform::from_translation(at),
+            ..Default::default()
+        })
+        .insert(Collider {
+            size: CLOUD_SIZE,
+            filter: CollisionFilter::TOP,
+            layers: CollisionLayers::WORLD,
+            ..Default::default()
+        })
+        .insert(SteamCloud {
+            spawned: Instant::now(),
+        })
+        .id();
+
+    let puff = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(CLOUD_SIZE),
+                ..Default::default()
+            },
+            texture,
+            transform: Transform::from_translation(at),
+            ..Default::default()
+        })
+        .insert(SteamPuff {
+            spawned: Instant::now(),
+        })
+        .id();
+
+    if let Some(room) = room {
+        commands.entity(room).push_children(&[cloud, puff]);
+    }
+}
+
+/// Fades out and removes the steam clouds and puffs
+pub fn steam_cloud_system(
+    mut commands: Commands,
+    mut cloud_query: Query<(&SteamCloud, &mut Sprite, Entity)>,
+    mut puff_query: Query<(&SteamPuff, &mut Sprite, &mut Transform, Entity), Without<SteamCloud>>,
+) {
+    for (cloud, mut sprite, entity) in cloud_query.iter_mut() {
+        let elapsed = cloud.spawned.elapsed();
+        if elapsed > CLOUD_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let remaining = CLOUD_LIFETIME - elapsed;
+        if remaining < CLOUD_FADE_DURATION {
+            sprite
+                .color
+                .set_a(remaining.as_secs_f32() / CLOUD_FADE_DURATION.as_secs_f32());
+        }
+    }
+
+    for (puff, mut sprite, mut transform, entity) in puff_query.iter_mut() {
+        let elapsed = puff.spawned.elapsed();
+        if elapsed > PUFF_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let progress = elapsed.as_secs_f32() / PUFF_LIFETIME.as_secs_f32();
+        transform.scale = Vec3::splat(1.0 + (PUFF_MAX_SCALE - 1.0) * progress);
+        sprite.color.set_a(1.0 - progress);
+    }
+}
+
 pub fn double_jump_land_system(
     mut land_events: EventReader<PlayerLandEvent>,
     mut query: Query<&mut PlayerDoubleJump>,
@@ -58,6 +233,8 @@ pub fn double_jump_land_system(
     {
         if let Ok(mut dbl_jump) = query.get_mut(*ent) {
             dbl_jump.can_dbl_jump = true;
+            // A press while falling would double jump right after leaving the ground again
+            dbl_jump.jump_buffer.clear();
         }
     }
 }
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::Instant};
+use serde::Deserialize;
+
+use crate::{
+    collision::{
+        triggers::{Trigger, TriggerEvent, TriggerEventKind},
+        BreakableCollider, Collider, CollisionEvent, CollisionLayers, MoveableCollider,
+    },
+    combat::Projectile,
+    enemies::EnemyHealth,
+    physics::VelocityMap,
+    player::{
+        abilities::{
+            input::{AbilityInput, InputMode},
+            registry::{AbilitySystems, RegisterAbility},
+            upgrades::{AbilityUpgrades, UpgradeTiers},
+            Ability, PlayerInventory,
+        },
+        MouseCursor,
+    },
+    util::deserialize_millis,
+};
+
+/// The `earth` ability
+#[derive(Debug)]
+pub struct ShootPlugin;
+
+impl Plugin for ShootPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_upgradable_ability::<PlayerShoot, EarthTier>(
+            "earth",
+            AbilitySystems {
+                update: SystemSet::new()
+                    .with_system(player_shoot_system)
+                    .with_system(player_shot_collision_system),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// The stats of the shooting ability per upgrade tier
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct EarthTier {
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub shoot_interval: Duration,
+    /// How many enemies a shot passes through before it stops
+    pub pierce: u32,
+}
+
+const PLAYER_SHOT_DAMAGE: u32 = 2;
+/// The damage added by a fully charged shot
+const PLAYER_SHOT_CHARGE_DAMAGE: u32 = 4;
+
+/// The shooting ability.
+/// Charging makes the shot bigger and stronger.
+#[derive(Debug, Component)]
+pub struct PlayerShoot {
+    last_shot: Instant,
+}
+
+impl Ability for PlayerShoot {
+    fn input_mode() -> InputMode {
+        InputMode::Charge {
+            full_charge: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl Default for PlayerShoot {
+    fn default() -> Self {
+        Self {
+            last_shot: Instant::now(),
+        }
+    }
+}
+
+pub fn player_shoot_system(
+    mut commands: Commands,
+    mut player_query: Query<(
+        &Transform,
+        &PlayerInventory,
+        &AbilityInput,
+        &AbilityUpgrades,
+        &mut PlayerShoot,
+    )>,
+    cursor_query: Query<&Transform, With<MouseCursor>>,
+    tiers: Res<UpgradeTiers<EarthTier>>,
+) {
+    const PLAYER_PROJECTILE_SPEED: f32 = 5.5;
+    const PLAYER_SHOT_SIZE: f32 = 4.0;
+
+    for (player_transform, inv, input, upgrades, mut player_shoot) in player_query.iter_mut() {
+        if let Some(charge) = input.trigger::<PlayerShoot>(inv) {
+            let stats = upgrades.stats::<PlayerShoot, _>(&tiers);
+            if player_shoot.last_shot.elapsed() < stats.shoot_interval {
+                continue;
+            }
+
+            player_shoot.last_shot = Instant::now();
+
+            for cursor in cursor_query.iter() {
+                let direction = -(player_transform.translation - cursor.translation)
+                    .normalize()
+                    .truncate();
+                let projectile = Projectile {
+                    speed: PLAYER_PROJECTILE_SPEED,
+                    direction,
+                    layers: CollisionLayers::PLAYER_PROJECTILE,
+                    mask: CollisionLayers::WORLD | CollisionLayers::ENEMY,
+                    vel_id: None,
+                };
+                let size = Vec2::splat(PLAYER_SHOT_SIZE * (1.0 + charge));
+                let damage =
+                    PLAYER_SHOT_DAMAGE + (PLAYER_SHOT_CHARGE_DAMAGE as f32 * charge).round() as u32;
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgb(0.0, 1.0, 1.0),
+                            custom_size: Some(size),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(player_transform.translation),
+                        ..Default::default()
+                    })
+                    // Walls are hit by colliding with them, enemies inside the trigger, so piercing
+                    // shots are not stopped by them
+                    .insert(Collider {
+                        size,
+                        layers: projectile.layers,
+                        mask: projectile.mask & CollisionLayers::WORLD,
+                        ..Default::default()
+                    })
+                    .insert(MoveableCollider)
+                    .insert(
+                        Trigger::new(size, Vec3::ZERO)
+                            .with_mask(projectile.mask & CollisionLayers::ENEMY),
+                    )
+                    .insert(VelocityMap::new())
+                    .insert(PlayerShotProjectile::new(damage, stats.pierce))
+                    .insert(projectile);
+            }
+        }
+    }
+}
+
+/// Component for projectiles that can break breakable walls
+#[derive(Debug, Component)]
+pub struct PlayerShotProjectile {
+    damage: u32,
+    /// How many more enemies the shot passes through
+    pierce: u32,
+    /// Whether the shot hit something it can't pass through and is despawned already
+    despawned: bool,
+    creation_time: Instant,
+}
+
+impl PlayerShotProjectile {
+    fn new(damage: u32, pierce: u32) -> Self {
+        Self {
+            damage,
+            pierce,
+            despawned: false,
+            creation_time: Instant::now(),
+        }
+    }
+}
+
+/// System that destroys breakable colliders and damages enemies hit by player projectiles.
+///
+/// Walls are hit by colliding with them, enemies by entering the trigger of the shot.
+pub fn player_shot_collision_system(
+    mut commands: Commands,
+    mut collision_reader: EventReader<CollisionEvent>,
+    mut trigger_reader: EventReader<TriggerEvent>,
+    mut shot_query: Query<(&mut PlayerShotProjectile, &Projectile, Entity)>,
+    mut target_query: Query<(
+        &Collider,
+        Option<&mut EnemyHealth>,
+        Option<&BreakableCollider>,
+    )>,
+) {
+    let collisions = collision_reader
+        .iter()
+        .map(|evt| (evt.moving_entity, evt.static_entity));
+    let triggers = trigger_reader
+        .iter()
+        .filter(|evt| evt.kind == TriggerEventKind::Enter)
+        .map(|evt| (evt.trigger, evt.entity));
+
+    for (shot_entity, target) in collisions.chain(triggers) {
+        let (mut shot, projectile) = match shot_query.get_mut(shot_entity) {
+            // Shots hitting multiple things at once are only despawned once
+            Ok((shot, projectile, _)) if !shot.despawned => (shot, projectile),
+            _ => continue,
+        };
+        let (collider, enemy_health, breakable) = match target_query.get_mut(target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if !collider.interacts_with_layers(projectile.layers, projectile.mask) {
+            continue;
+        }
+
+        if breakable.is_some() {
+            commands.entity(target).despawn();
+        }
+
+        if let Some(mut health) = enemy_health {
+            *health -= shot.damage;
+
+            // Piercing shots keep flying through the enemies
+            if shot.pierce > 0 && breakable.is_none() {
+                shot.pierce -= 1;
+                continue;
+            }
+        }
+
+        shot.despawned = true;
+        commands.entity(shot_entity).despawn();
+    }
+
+    for (shot, _, shot_entity) in shot_query.iter() {
+        if !shot.despawned && shot.creation_time.elapsed() > Duration::from_secs(30) {
+            commands.entity(shot_entity).despawn();
+        }
+    }
+}
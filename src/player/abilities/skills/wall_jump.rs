@@ -1,22 +1,69 @@
-use std::any::TypeId;
+use std::time::Duration;
 
 use bevy::{prelude::*, sprite::collide_aabb::Collision};
+use serde::Deserialize;
 
 use crate::{
-    physics::Gravity,
+    controls::Action,
+    physics::{Decay, Gravity, ImpulseId, VelocityMap, GRAVITY},
     player::{
-        abilities::{Ability, EquipSlot, PlayerInventory},
+        abilities::{
+            input::AbilityInput,
+            registry::{AbilitySystems, RegisterAbility},
+            upgrades::{AbilityUpgrades, UpgradeTiers},
+            Ability, PlayerInventory,
+        },
+        config::PlayerConfig,
+        player_fall_system,
+        state::{PlayerState, PlayerStateMachine},
         PlayerCollisionEvent, PlayerMovement,
     },
+    util::TimeWindow,
 };
 
+/// The `water` ability
+#[derive(Debug)]
+pub struct WallJumpPlugin;
+
+impl Plugin for WallJumpPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_upgradable_ability::<PlayerWallJump, WaterTier>(
+            "water",
+            AbilitySystems {
+                late_update: SystemSet::new()
+                    .with_system(player_wall_jump_system.after(player_fall_system)),
+                post_collision: SystemSet::new().with_system(wall_jump_collision_system),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// The stats of the wall jump ability per upgrade tier
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct WaterTier {
+    /// The speed pushing the player away from the wall
+    pub push: f32,
+    pub power: f32,
+}
+
 #[derive(Component, Debug, Default)]
 pub struct PlayerWallJump {
+    /// The side of the wall touched last
     wall_side: Option<WallSide>,
-    wall_jumped: bool,
+    /// When the wall was touched
+    wall_touched: TimeWindow,
+    /// When the wall jump button was pressed
+    jump_buffer: TimeWindow,
+    /// The push away from the wall, movement is blocked while it is active
+    wall_push: Option<ImpulseId>,
 }
 
-impl Ability for PlayerWallJump {}
+impl Ability for PlayerWallJump {
+    fn owns_state(state: PlayerState) -> bool {
+        state == PlayerState::WallJumping
+    }
+}
 
 /// On which side of a wall we are
 #[derive(Debug, Clone, Copy)]
@@ -26,44 +73,90 @@ enum WallSide {
 }
 
 pub fn player_wall_jump_system(
-    mouse_input: ResMut<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
     mut query: Query<(
         &mut Gravity,
         &mut PlayerMovement,
+        &mut PlayerStateMachine,
         &mut PlayerWallJump,
+        &mut VelocityMap,
         &PlayerInventory,
+        &AbilityInput,
+        &AbilityUpgrades,
     )>,
+    config: Res<PlayerConfig>,
+    tiers: Res<UpgradeTiers<WaterTier>>,
 ) {
-    const WALL_JUMP_POWER: Vec2 = Vec2::new(4.0, 7.0);
-
-    for (mut grav, mut player, mut wall_jump, inv) in query.iter_mut() {
-        if let Some(mouse_button) = inv
-            .get_equipped_at::<PlayerWallJump>()
-            .map(EquipSlot::to_mouse_btn)
-        {
-            if mouse_input.just_pressed(mouse_button) {
-                match wall_jump.wall_side {
-                    Some(WallSide::Right) => {
-                        grav.velocity = Vec2::ZERO;
-                        player.velocity = WALL_JUMP_POWER;
-                        wall_jump.wall_jumped = true;
-                        player
-                            .move_forbid_set
-                            .insert(TypeId::of::<PlayerWallJump>());
-                    }
-                    Some(WallSide::Left) => {
-                        grav.velocity = Vec2::ZERO;
-                        player.velocity.y = WALL_JUMP_POWER.y;
-                        player.velocity.x = -WALL_JUMP_POWER.x;
-                        wall_jump.wall_jumped = true;
-                        player
-                            .move_forbid_set
-                            .insert(TypeId::of::<PlayerWallJump>());
-                    }
-                    None => (),
+    const WALL_JUMP_PUSH_DURATION: Duration = Duration::from_millis(600);
+    // How long after touching the wall the player still slides down along it
+    const WALL_CONTACT_TIME: Duration = Duration::from_millis(100);
+    const WALL_SLIDE_SPEED: f32 = GRAVITY * 1.5;
+
+    for (
+        mut grav,
+        mut player,
+        mut state_machine,
+        mut wall_jump,
+        mut vel_map,
+        inv,
+        input,
+        upgrades,
+    ) in query.iter_mut()
+    {
+        let stats = upgrades.stats::<PlayerWallJump, _>(&tiers);
+
+        if let Some(wall_push) = wall_jump.wall_push {
+            if !vel_map.is_active(wall_push) {
+                if state_machine.state() == PlayerState::WallJumping {
+                    state_machine.request(PlayerState::Airborne);
+                }
+                wall_jump.wall_push = None;
+            }
+        }
+
+        if inv.get_equipped_at::<PlayerWallJump>().is_some() {
+            if input.trigger::<PlayerWallJump>(inv).is_some() {
+                wall_jump.jump_buffer.start();
+            }
+
+            if wall_jump.jump_buffer.is_within(config.jump.buffer_time)
+                && wall_jump.wall_touched.is_within(config.jump.coyote_time)
+            {
+                let push_x = match wall_jump.wall_side {
+                    Some(WallSide::Right) => stats.push,
+                    Some(WallSide::Left) => -stats.push,
+                    None => continue,
+                };
+
+                if !state_machine.request(PlayerState::WallJumping) {
+                    continue;
                 }
 
+                grav.velocity = Vec2::ZERO;
+                player.velocity = Vec2::new(0.0, stats.power);
+                wall_jump.wall_push = Some(vel_map.add_impulse(
+                    Vec2::new(push_x, 0.0),
+                    WALL_JUMP_PUSH_DURATION,
+                    Decay::Linear,
+                ));
                 wall_jump.wall_side = None;
+                wall_jump.wall_touched.clear();
+                wall_jump.jump_buffer.clear();
+                continue;
+            }
+
+            // Sliding down slowly while pressing into the wall
+            let pressing_into_wall = match wall_jump.wall_side {
+                Some(WallSide::Left) => actions.pressed(Action::MoveRight),
+                Some(WallSide::Right) => actions.pressed(Action::MoveLeft),
+                None => false,
+            };
+            if pressing_into_wall
+                && wall_jump.wall_touched.is_within(WALL_CONTACT_TIME)
+                && state_machine.state() == PlayerState::Airborne
+                && player.velocity.y <= 0.0
+            {
+                grav.velocity.y = grav.velocity.y.max(-WALL_SLIDE_SPEED);
             }
         }
     }
@@ -71,30 +164,18 @@ pub fn player_wall_jump_system(
 
 pub fn wall_jump_collision_system(
     mut events: EventReader<PlayerCollisionEvent>,
-    mut player_query: Query<(&mut PlayerWallJump, &mut PlayerMovement)>,
+    mut player_query: Query<&mut PlayerWallJump>,
 ) {
-    // TODO this currently handles all active players as one (currently only one)
-    let mut new_wall_side = None;
-    let mut landed = false;
     for evt in events.iter() {
-        new_wall_side = match evt.collision_side {
-            Collision::Left => Some(WallSide::Left),
-            Collision::Right => Some(WallSide::Right),
-            Collision::Top => {
-                landed = true;
-                None
-            }
-            _ => None,
+        let wall_side = match evt.collision_side {
+            Collision::Left => WallSide::Left,
+            Collision::Right => WallSide::Right,
+            _ => continue,
         };
-    }
 
-    for (mut wall_jump, mut player) in player_query.iter_mut() {
-        if wall_jump.wall_jumped && landed {
-            player
-                .move_forbid_set
-                .remove(&TypeId::of::<PlayerWallJump>());
-            wall_jump.wall_jumped = false;
+        if let Ok(mut wall_jump) = player_query.get_mut(evt.player_entity) {
+            wall_jump.wall_side = Some(wall_side);
+            wall_jump.wall_touched.start();
         }
-        wall_jump.wall_side = new_wall_side;
     }
 }
@@ -0,0 +1,95 @@
+//! Upgrading the abilities by collecting upgrade items, each upgrade moves the ability one tier up.
+//!
+//! The stats of every tier are part of the [`UpgradesConfig`](crate::player::config::UpgradesConfig),
+//! each ability plugin loads its own [`UpgradeTiers`].
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{registry::AbilityRegistry, Ability, AbilityId, AbilityItem};
+use crate::{
+    collision::triggers::{TriggerEvent, TriggerEventKind},
+    map::{MapManager, TileId},
+};
+
+/// The upgrade tiers the player reached, abilities without upgrades are at tier 0
+#[derive(Component, Debug, Default)]
+pub struct AbilityUpgrades(HashMap<AbilityId, usize>);
+
+impl AbilityUpgrades {
+    pub fn tier<A: Ability>(&self) -> usize {
+        self.0.get(&A::ability_id()).copied().unwrap_or(0)
+    }
+
+    /// Move the ability one tier up, returns false if `max_tier` is reached already
+    pub fn upgrade(&mut self, id: AbilityId, max_tier: usize) -> bool {
+        let tier = self.0.entry(id).or_insert(0);
+        if *tier >= max_tier {
+            return false;
+        }
+
+        *tier += 1;
+        true
+    }
+
+    /// The stats of the tier the ability `A` is at
+    pub fn stats<'a, A: Ability, T>(&self, tiers: &'a UpgradeTiers<T>) -> &'a T {
+        tiers.0.get(self.tier::<A>()).unwrap_or_else(|| {
+            panic!(
+                "The ability {} has no upgrade tier {}",
+                std::any::type_name::<A>(),
+                self.tier::<A>()
+            )
+        })
+    }
+}
+
+/// The stats of an ability per upgrade tier, the first tier is the ability without upgrades
+#[derive(Debug)]
+pub struct UpgradeTiers<T>(pub(super) Vec<T>);
+
+/// An upgrade item, collected by touching it
+#[derive(Component, Debug)]
+pub struct CollectibleUpgradeTrigger {
+    item: AbilityItem,
+    /// The tile of the item, so it is not spawned again with its room
+    tile: TileId,
+}
+
+impl CollectibleUpgradeTrigger {
+    pub fn new(item: AbilityItem, tile: TileId) -> Self {
+        Self { item, tile }
+    }
+}
+
+/// Upgrades the ability when the player touches its upgrade item.
+/// Items of fully upgraded abilities are left in place, collected items are gone for good.
+pub fn collect_upgrade_system(
+    mut commands: Commands,
+    mut trigger_reader: EventReader<TriggerEvent>,
+    collectible_query: Query<&CollectibleUpgradeTrigger>,
+    mut player_query: Query<&mut AbilityUpgrades>,
+    registry: Res<AbilityRegistry>,
+    mut map: ResMut<MapManager>,
+) {
+    for evt in trigger_reader.iter() {
+        if evt.kind != TriggerEventKind::Enter {
+            continue;
+        }
+
+        let (collectible, mut upgrades) = match (
+            collectible_query.get(evt.trigger),
+            player_query.get_mut(evt.entity),
+        ) {
+            (Ok(collectible), Ok(upgrades)) => (collectible, upgrades),
+            _ => continue,
+        };
+
+        let ability = registry
+            .get(&collectible.item)
+            .expect("The abilities of the items are checked when loading the room");
+        if upgrades.upgrade(*ability.id(), ability.max_tier()) {
+            commands.entity(evt.trigger).despawn();
+            map.collect(collectible.tile.clone());
+        }
+    }
+}
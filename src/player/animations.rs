@@ -0,0 +1,73 @@
+//! The animations of the player, chosen by the state and movement of the player
+
+use bevy::prelude::*;
+
+use super::{
+    state::{PlayerState, PlayerStateMachine},
+    PlayerMovement,
+};
+use crate::{
+    animation::{Animation, Animator},
+    asset_loaders::{cache::AssetCache, EmbeddedAssets},
+    physics::VelocityMap,
+};
+
+#[derive(Component, Debug)]
+pub struct PlayerAnimations {
+    pub idle: Animation,
+    walk: Animation,
+    jump: Animation,
+    fall: Animation,
+    wall_jump: Animation,
+    crouch: Animation,
+    dash: Animation,
+}
+
+impl PlayerAnimations {
+    /// Load all animations from `animations/player`
+    pub fn load(asset_cache: &mut AssetCache<EmbeddedAssets>, assets: &mut Assets<Image>) -> Self {
+        let mut load = |name: &str| {
+            let path = format!("animations/player/{}.toml", name);
+            asset_cache
+                .load_animation(assets, &path)
+                .unwrap_or_else(|e| {
+                    panic!("The player animation {} could not be loaded: {}", path, e)
+                })
+        };
+
+        Self {
+            idle: load("idle"),
+            walk: load("walk"),
+            jump: load("jump"),
+            fall: load("fall"),
+            wall_jump: load("wall_jump"),
+            crouch: load("crouch"),
+            dash: load("dash"),
+        }
+    }
+}
+
+/// Plays the animation matching what the player is doing
+pub fn player_animation_system(
+    mut query: Query<(
+        &mut Animator,
+        &PlayerAnimations,
+        &PlayerStateMachine,
+        &PlayerMovement,
+        &VelocityMap,
+    )>,
+) {
+    for (mut animator, animations, state_machine, movement, vel_map) in query.iter_mut() {
+        let animation = match state_machine.state() {
+            PlayerState::Grounded if movement.velocity.x.abs() > f32::EPSILON => &animations.walk,
+            PlayerState::Grounded => &animations.idle,
+            PlayerState::Airborne if vel_map.last_velocity().y > 0.0 => &animations.jump,
+            PlayerState::Airborne | PlayerState::Stunned | PlayerState::Dead => &animations.fall,
+            PlayerState::WallJumping | PlayerState::Hanging => &animations.wall_jump,
+            PlayerState::Crouched { .. } => &animations.crouch,
+            PlayerState::Dashing => &animations.dash,
+        };
+
+        animator.play(animation);
+    }
+}
@@ -0,0 +1,106 @@
+//! The parameters of the player movement, health and abilities, loaded from the `player.toml`
+
+use std::time::Duration;
+
+use bevy::{ecs::world::FromWorld, prelude::World, utils::HashMap};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    asset_loaders::{EmbeddedAssetLoader, EmbeddedData},
+    player::abilities::{AbilityItem, DashMode},
+    util::deserialize_millis,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct PlayerConfig {
+    pub movement: MovementConfig,
+    pub jump: JumpConfig,
+    pub health: HealthConfig,
+    pub dash: DashConfig,
+    pub upgrades: UpgradesConfig,
+}
+
+/// Loads the `player.toml`, so whichever plugin needs the config first can initialize it
+impl FromWorld for PlayerConfig {
+    fn from_world(_world: &mut World) -> Self {
+        Self::load("player.toml")
+    }
+}
+
+impl PlayerConfig {
+    pub fn load(filename: &str) -> Self {
+        let data = EmbeddedData::load(filename).unwrap_or_else(|e| {
+            panic!("The player config({}) could not be loaded: {}", filename, e)
+        });
+
+        toml::from_slice(&data).unwrap_or_else(|e| {
+            panic!(
+                "There was an error parsing the player config({}): {}",
+                filename, e
+            )
+        })
+    }
+}
+
+/// The horizontal movement, all speeds are in pixels per frame
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct MovementConfig {
+    pub speed: f32,
+    pub swim_speed: f32,
+    /// The speed gained per frame on the ground, scaled by the friction of the ground
+    pub acceleration: f32,
+    /// The speed lost per frame on the ground when not moving or turning around
+    pub deceleration: f32,
+    pub air_acceleration: f32,
+    pub air_deceleration: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct JumpConfig {
+    pub power: f32,
+    /// The factor of the upwards velocity kept when releasing the jump key early
+    pub cut_multiplier: f32,
+    /// How much faster the player falls after the highest point of a jump
+    pub fall_multiplier: f32,
+    /// How long a jump is still possible after leaving the ground or a wall
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub coyote_time: Duration,
+    /// How long a jump input is remembered while a jump is not possible
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub buffer_time: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct HealthConfig {
+    pub max: u32,
+    /// How long the player can't be hurt again after being hurt
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub invulnerability_time: Duration,
+    /// How long the player is knocked back and can't move after being hurt
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub knockback_time: Duration,
+    /// How long the player stays dead before respawning
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub respawn_delay: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct DashConfig {
+    pub mode: DashMode,
+}
+
+/// The stats of the abilities per upgrade tier by the id of their item, the first tier is the
+/// ability without upgrades. Each ability plugin loads its own tiers.
+#[derive(Deserialize, Debug)]
+pub struct UpgradesConfig(HashMap<AbilityItem, toml::Value>);
+
+impl UpgradesConfig {
+    pub fn tiers<T: DeserializeOwned>(
+        &self,
+        item: &AbilityItem,
+    ) -> Result<Vec<T>, toml::de::Error> {
+        self.0
+            .get(item)
+            .map_or_else(|| Ok(Vec::new()), |tiers| tiers.clone().try_into())
+    }
+}
@@ -0,0 +1,129 @@
+//! The health of the player, taking damage and dying
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{
+    config::PlayerConfig,
+    state::{PlayerState, PlayerStateMachine},
+    PlayerMovement,
+};
+use crate::{
+    combat::DamageEvent,
+    physics::{Decay, Gravity, ImpulseId, VelocityMap},
+    util::TimeWindow,
+};
+
+/// How long the player is visible or invisible while flashing
+const FLASH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Component, Debug)]
+pub struct PlayerHealth {
+    current: u32,
+    max: u32,
+    /// When the player was hurt, the player is invulnerable for a while after it
+    hurt: TimeWindow,
+    /// The knockback of the last hit, the player is stunned while it is active
+    knockback: Option<ImpulseId>,
+}
+
+impl PlayerHealth {
+    pub fn new(max: u32) -> Self {
+        Self {
+            current: max,
+            max,
+            hurt: TimeWindow::default(),
+            knockback: None,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Heal the player completely
+    pub fn restore(&mut self) {
+        self.current = self.max;
+        self.hurt.clear();
+        self.knockback = None;
+    }
+}
+
+/// Sent when the health of the player drops to zero
+#[derive(Debug)]
+pub struct PlayerDeathEvent(pub Entity);
+
+/// Hurts the player, knocking them back and stunning them for a short time
+pub fn player_damage_system(
+    mut damage_reader: EventReader<DamageEvent>,
+    mut query: Query<(
+        &mut PlayerHealth,
+        &mut PlayerStateMachine,
+        &mut PlayerMovement,
+        &mut Gravity,
+        &mut VelocityMap,
+    )>,
+    mut death_writer: EventWriter<PlayerDeathEvent>,
+    config: Res<PlayerConfig>,
+) {
+    for evt in damage_reader.iter() {
+        if let Ok((mut health, mut state_machine, mut movement, mut grav, mut vel_map)) =
+            query.get_mut(evt.target)
+        {
+            if health.is_dead() || health.hurt.is_within(config.health.invulnerability_time) {
+                continue;
+            }
+
+            health.current = health.current.saturating_sub(evt.amount);
+            health.hurt.start();
+
+            if health.is_dead() {
+                state_machine.request(PlayerState::Dead);
+                death_writer.send(PlayerDeathEvent(evt.target));
+                continue;
+            }
+
+            if state_machine.request(PlayerState::Stunned) {
+                movement.velocity = Vec2::ZERO;
+                grav.velocity = Vec2::ZERO;
+                health.knockback = Some(vel_map.add_impulse(
+                    evt.knockback,
+                    config.health.knockback_time,
+                    Decay::EaseOut,
+                ));
+            }
+        }
+    }
+}
+
+/// Gives the control back to the player after the knockback ended
+pub fn player_stun_system(
+    mut query: Query<(&mut PlayerHealth, &mut PlayerStateMachine, &VelocityMap)>,
+) {
+    for (mut health, mut state_machine, vel_map) in query.iter_mut() {
+        if let Some(knockback) = health.knockback {
+            if !vel_map.is_active(knockback) {
+                if state_machine.state() == PlayerState::Stunned {
+                    state_machine.request(PlayerState::Airborne);
+                }
+                health.knockback = None;
+            }
+        }
+    }
+}
+
+/// Lets the player flash while invulnerable
+pub fn player_flash_system(
+    mut query: Query<(&PlayerHealth, &mut Visibility)>,
+    config: Res<PlayerConfig>,
+) {
+    for (health, mut visibility) in query.iter_mut() {
+        visibility.is_visible = match health.hurt.elapsed() {
+            Some(elapsed) if !health.is_dead() && elapsed <= config.health.invulnerability_time => {
+                (elapsed.as_millis() / FLASH_INTERVAL.as_millis()) % 2 == 1
+            }
+            _ => true,
+        };
+    }
+}
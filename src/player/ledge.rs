@@ -0,0 +1,176 @@
+//! Grabbing ledges when the top of the player reaches them, then climbing up or dropping down
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{
+    state::{PlayerState, PlayerStateMachine},
+    PlayerMovement,
+};
+use crate::{
+    collision::{
+        raycast::{CastFilter, ColliderCast},
+        Collider, CollisionLayers,
+    },
+    controls::Action,
+    physics::{Gravity, VelocityMap},
+    util::TimeWindow,
+};
+
+/// How far a wall can be in front of the player to grab its ledge
+const LEDGE_REACH: f32 = 2.0;
+/// How far the top of the player can be away from a ledge to grab it
+const LEDGE_GRAB_HEIGHT: f32 = 4.0;
+/// How long the player can't grab a ledge after letting go of one
+const LEDGE_REGRAB_TIME: Duration = Duration::from_millis(300);
+
+#[derive(Component, Debug, Default)]
+pub struct PlayerLedgeGrab {
+    /// The ledge the player hangs at
+    ledge: Option<Ledge>,
+    /// When the player let go of the last ledge
+    released: TimeWindow,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ledge {
+    /// 1 if the ledge is right of the player
+    dir: f32,
+    /// Where the player hangs at the ledge
+    hang_position: Vec2,
+    /// Where the player stands after climbing onto the ledge
+    climb_position: Vec2,
+}
+
+/// Grabs a ledge when the player falls along a wall towards it and there is room to climb onto it
+pub fn player_ledge_grab_system(
+    actions: Res<Input<Action>>,
+    mut query: Query<(
+        &mut PlayerLedgeGrab,
+        &mut PlayerStateMachine,
+        &Transform,
+        &Collider,
+        &VelocityMap,
+        Entity,
+    )>,
+    colliders: ColliderCast,
+) {
+    for (mut ledge_grab, mut state_machine, transform, collider, vel_map, entity) in
+        query.iter_mut()
+    {
+        if state_machine.state() != PlayerState::Airborne
+            || vel_map.last_velocity().y > 0.0
+            || ledge_grab.released.is_within(LEDGE_REGRAB_TIME)
+        {
+            continue;
+        }
+
+        let dir = match (
+            actions.pressed(Action::MoveLeft),
+            actions.pressed(Action::MoveRight),
+        ) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => continue,
+        };
+
+        let position = transform.translation.truncate();
+        let half_size = collider.size / 2.0;
+        let filter = CastFilter::new(collider.layers, CollisionLayers::WORLD).ignoring(entity);
+        let front_x = position.x + dir * half_size.x;
+        let above_y = position.y + half_size.y + LEDGE_GRAB_HEIGHT;
+
+        // The space above the ledge has to be free to climb onto it
+        if colliders
+            .raycast(
+                Vec2::new(front_x, above_y),
+                Vec2::new(dir, 0.0),
+                LEDGE_REACH,
+                &filter,
+            )
+            .is_some()
+        {
+            continue;
+        }
+
+        let ledge_y = match colliders.raycast(
+            Vec2::new(front_x + dir * LEDGE_REACH, above_y),
+            Vec2::NEG_Y,
+            LEDGE_GRAB_HEIGHT * 2.0,
+            &filter,
+        ) {
+            Some(hit) => hit.point.y,
+            None => continue,
+        };
+
+        // The side of the wall right below the ledge
+        let wall_x = match colliders.raycast(
+            Vec2::new(position.x, ledge_y - 1.0),
+            Vec2::new(dir, 0.0),
+            half_size.x + LEDGE_REACH,
+            &filter,
+        ) {
+            Some(hit) => hit.point.x,
+            None => continue,
+        };
+
+        if state_machine.request(PlayerState::Hanging) {
+            ledge_grab.ledge = Some(Ledge {
+                dir,
+                hang_position: Vec2::new(wall_x - dir * half_size.x, ledge_y - half_size.y),
+                climb_position: Vec2::new(wall_x + dir * half_size.x, ledge_y + half_size.y),
+            });
+        }
+    }
+}
+
+/// Keeps the player at the ledge until they climb up by jumping or drop down by moving away
+pub fn player_ledge_hang_system(
+    actions: Res<Input<Action>>,
+    mut query: Query<(
+        &mut PlayerLedgeGrab,
+        &mut PlayerStateMachine,
+        &mut Transform,
+        &mut PlayerMovement,
+        &mut Gravity,
+    )>,
+) {
+    for (mut ledge_grab, mut state_machine, mut transform, mut movement, mut grav) in
+        query.iter_mut()
+    {
+        let ledge = match ledge_grab.ledge {
+            Some(ledge) => ledge,
+            None => continue,
+        };
+
+        // Hurt while hanging
+        if state_machine.state() != PlayerState::Hanging {
+            ledge_grab.ledge = None;
+            continue;
+        }
+
+        movement.velocity = Vec2::ZERO;
+        grav.velocity = Vec2::ZERO;
+
+        let away = if ledge.dir > 0.0 {
+            Action::MoveLeft
+        } else {
+            Action::MoveRight
+        };
+
+        if actions.just_pressed(Action::Jump) {
+            transform.translation = ledge.climb_position.extend(transform.translation.z);
+            // The climb uses up the jump, the player doesn't jump off the ledge right away
+            movement.jump_buffer.clear();
+            movement.jump_blocked = true;
+        } else if !actions.just_pressed(away) {
+            transform.translation = ledge.hang_position.extend(transform.translation.z);
+            continue;
+        }
+
+        state_machine.request(PlayerState::Airborne);
+        ledge_grab.ledge = None;
+        ledge_grab.released.start();
+    }
+}
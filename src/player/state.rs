@@ -0,0 +1,212 @@
+//! The state of the player, deciding which movement and abilities are possible
+
+use bevy::prelude::*;
+
+use crate::physics::GroundContact;
+
+/// Everything the player can be doing, only one at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlayerState {
+    Grounded,
+    Airborne,
+    Dashing,
+    WallJumping,
+    /// Falling fast while crouching in the air, staying small on the ground
+    Crouched {
+        grounded: bool,
+    },
+    /// Hanging at a ledge, until climbing up or dropping down
+    Hanging,
+    /// Knocked back after being hurt
+    Stunned,
+    /// Waiting to respawn
+    Dead,
+}
+
+impl PlayerState {
+    /// Whether the player can switch from this state to `next`
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match (self, next) {
+            (Self::Dead, _) => false,
+            (_, Self::Dead | Self::Stunned) => true,
+            (Self::Stunned, next) => matches!(next, Self::Grounded | Self::Airborne),
+            (Self::Grounded | Self::Airborne, _)
+            | (_, Self::Grounded | Self::Airborne)
+            | (Self::WallJumping, Self::WallJumping)
+            | (Self::Crouched { .. }, Self::Crouched { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the player moves by input in this state
+    pub fn has_control(self) -> bool {
+        matches!(
+            self,
+            Self::Grounded | Self::Airborne | Self::Crouched { .. }
+        )
+    }
+
+    /// Whether the player can start a jump in this state
+    pub fn can_jump(self) -> bool {
+        matches!(self, Self::Grounded | Self::Airborne)
+    }
+}
+
+/// The current state of the player, abilities request transitions to their states
+#[derive(Component, Debug)]
+pub struct PlayerStateMachine {
+    state: PlayerState,
+    /// Transitions not yet sent as [`PlayerStateEvent`]s
+    transitions: Vec<(PlayerState, PlayerState)>,
+}
+
+impl Default for PlayerStateMachine {
+    fn default() -> Self {
+        Self {
+            state: PlayerState::Airborne,
+            transitions: Vec::new(),
+        }
+    }
+}
+
+impl PlayerStateMachine {
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// Switch to `next` if possible, returns whether the player is in the `next` state now
+    pub fn request(&mut self, next: PlayerState) -> bool {
+        if self.state == next {
+            return true;
+        }
+
+        if !self.state.can_transition_to(next) {
+            return false;
+        }
+
+        self.transitions.push((self.state, next));
+        self.state = next;
+        true
+    }
+
+    /// Leave any state, even [`PlayerState::Dead`], when the player is respawned
+    pub fn respawn(&mut self) {
+        self.transitions.push((self.state, PlayerState::Airborne));
+        self.state = PlayerState::Airborne;
+    }
+}
+
+/// Sent for every transition of the player state
+#[derive(Debug)]
+pub struct PlayerStateEvent {
+    pub player: Entity,
+    pub from: PlayerState,
+    pub to: PlayerState,
+}
+
+/// Switches between grounded and airborne, all other states are left by their abilities
+pub fn player_ground_state_system(mut query: Query<(&mut PlayerStateMachine, &GroundContact)>) {
+    for (mut state_machine, ground) in query.iter_mut() {
+        match (state_machine.state(), ground.is_grounded()) {
+            (PlayerState::Airborne, true) => {
+                state_machine.request(PlayerState::Grounded);
+            }
+            (PlayerState::Grounded, false) => {
+                state_machine.request(PlayerState::Airborne);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Sends the transitions of this frame as [`PlayerStateEvent`]s
+pub fn player_state_event_system(
+    mut query: Query<(&mut PlayerStateMachine, Entity)>,
+    mut state_writer: EventWriter<PlayerStateEvent>,
+) {
+    for (mut state_machine, player) in query.iter_mut() {
+        for (from, to) in state_machine.transitions.drain(..) {
+            state_writer.send(PlayerStateEvent { player, from, to });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlayerState::{self, *};
+
+    const ALL: [PlayerState; 9] = [
+        Grounded,
+        Airborne,
+        Dashing,
+        WallJumping,
+        Crouched { grounded: false },
+        Crouched { grounded: true },
+        Hanging,
+        Stunned,
+        Dead,
+    ];
+
+    #[test]
+    fn every_state_can_be_left() {
+        for state in ALL {
+            if state != Dead {
+                assert!(state.can_transition_to(Airborne), "{:?} is stuck", state);
+            }
+        }
+    }
+
+    #[test]
+    fn dead_is_only_left_by_respawning() {
+        for next in ALL {
+            assert!(!Dead.can_transition_to(next));
+        }
+    }
+
+    #[test]
+    fn hurting_interrupts_everything() {
+        for state in ALL {
+            if state != Dead {
+                assert!(state.can_transition_to(Stunned));
+                assert!(state.can_transition_to(Dead));
+            }
+        }
+    }
+
+    #[test]
+    fn stunned_only_recovers_to_moving() {
+        for next in ALL {
+            let expected = matches!(next, Grounded | Airborne | Stunned | Dead);
+            assert_eq!(Stunned.can_transition_to(next), expected, "{:?}", next);
+        }
+    }
+
+    #[test]
+    fn abilities_do_not_interrupt_each_other() {
+        assert!(!Dashing.can_transition_to(WallJumping));
+        assert!(!Dashing.can_transition_to(Crouched { grounded: false }));
+        assert!(!WallJumping.can_transition_to(Dashing));
+        assert!(!Hanging.can_transition_to(Dashing));
+        assert!(Crouched { grounded: false }.can_transition_to(Crouched { grounded: true }));
+        assert!(Grounded.can_transition_to(Dashing));
+        assert!(Airborne.can_transition_to(Hanging));
+    }
+
+    #[test]
+    fn control_and_jumping() {
+        for state in ALL {
+            assert_eq!(
+                state.has_control(),
+                matches!(state, Grounded | Airborne | Crouched { .. }),
+                "{:?}",
+                state
+            );
+            assert_eq!(
+                state.can_jump(),
+                matches!(state, Grounded | Airborne),
+                "{:?}",
+                state
+            );
+        }
+    }
+}
@@ -1,6 +1,54 @@
 //! Utility functions
 
+use std::time::Duration;
+
+use bevy::utils::Instant;
+use serde::{Deserialize, Deserializer};
+
 // pub fn world_to_screen_point(cam: &Camera, ) {
 
 // }
 // x
+
+/// Remembers when something happened last, to check if it happened recently
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeWindow(Option<Instant>);
+
+impl TimeWindow {
+    /// Remember that it happened now
+    pub fn start(&mut self) {
+        self.0 = Some(Instant::now());
+    }
+
+    /// Forget that it happened
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+
+    /// How long ago it happened
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.0.map(|start| start.elapsed())
+    }
+
+    /// Whether it happened at most `window` ago
+    pub fn is_within(&self, window: Duration) -> bool {
+        self.elapsed().map_or(false, |elapsed| elapsed <= window)
+    }
+}
+
+/// Move `current` towards `target` by at most `max_delta`
+pub fn move_towards(current: f32, target: f32, max_delta: f32) -> f32 {
+    let delta = target - current;
+    if delta.abs() <= max_delta {
+        target
+    } else {
+        current + delta.signum() * max_delta
+    }
+}
+
+/// Deserialize a [`Duration`] from a number of milliseconds
+pub fn deserialize_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}